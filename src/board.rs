@@ -0,0 +1,152 @@
+use crate::tetriminos::{create_tetrimino, id_from_letter, letter_from_id, Tetrimino};
+
+/// Prints a board one row per line from the top, `.` for empty cells and the
+/// piece letters for blocks, `G` being garbage. The active piece, if any, is
+/// drawn over it in lowercase:
+///
+/// ```text
+/// ....t.....
+/// ...ttt....
+/// GGGG.GGGGG
+/// ```
+pub fn to_text(game_map: &[Vec<u8>], piece: Option<&Tetrimino>) -> String {
+    let mut lines: Vec<Vec<char>> = game_map
+        .iter()
+        .map(|line| line.iter().map(|case| letter_from_id(*case)).collect())
+        .collect();
+    if let Some(piece) = piece {
+        for (x, y, case) in piece.cells(piece.current_state as usize, piece.x, piece.y) {
+            if let Some(letter) = lines.get_mut(y).and_then(|line| line.get_mut(x as usize)) {
+                *letter = letter_from_id(case).to_ascii_lowercase();
+            }
+        }
+    }
+    lines.iter().map(|line| line.iter().collect::<String>() + "\n").collect()
+}
+
+/// Reads a board printed by `to_text`. Blank lines and lines starting with
+/// `#` are skipped, so boards can be pasted with a comment. Lowercase letters
+/// must form a single piece in one of its states, which is returned at that
+/// position with its cells left empty on the board.
+pub fn parse(text: &str) -> Result<(Vec<Vec<u8>>, Option<Tetrimino>), String> {
+    let mut game_map = Vec::new();
+    let mut active = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let y = game_map.len();
+        let row = line
+            .chars()
+            .enumerate()
+            .map(|(x, letter)| match (letter, id_from_letter(letter)) {
+                ('.', _) => Ok(0),
+                (letter, Some(id)) if letter.is_ascii_lowercase() => {
+                    active.push((x as isize, y, id));
+                    Ok(0)
+                }
+                (_, Some(id)) => Ok(id),
+                (letter, None) => Err(format!("Unknown cell: {}", letter)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        game_map.push(row);
+    }
+
+    let width = game_map.first().map_or(0, Vec::len);
+    if width == 0 {
+        return Err("The board is empty".to_owned());
+    }
+    if game_map.iter().any(|line| line.len() != width) {
+        return Err("Every row of the board must have the same number of cells".to_owned());
+    }
+    if active.is_empty() {
+        return Ok((game_map, None));
+    }
+    let piece = find_piece(&active).ok_or("The lowercase cells do not form a piece")?;
+    Ok((game_map, Some(piece)))
+}
+
+/// The piece whose cells are exactly `active`, in any state and at either
+/// scale.
+pub fn find_piece(active: &[(isize, usize, u8)]) -> Option<Tetrimino> {
+    let id = active.first()?.2;
+    if id > 7 || active.iter().any(|cell| cell.2 != id) {
+        return None;
+    }
+    let mut active = active.to_vec();
+    active.sort();
+    let left = active.iter().map(|cell| cell.0).min()?;
+    let top = active.iter().map(|cell| cell.1).min()?;
+
+    for scale in [1, 2] {
+        let mut piece = create_tetrimino(id);
+        piece.scale = scale;
+        for state in 0..piece.states.len() {
+            // Place the state so that its own top left block lines up.
+            let cells = piece.cells(state, 0, 0);
+            let (Some(min_x), Some(min_y)) = (cells.iter().map(|cell| cell.0).min(), cells.iter().map(|cell| cell.1).min())
+            else {
+                continue;
+            };
+            let Some(y) = top.checked_sub(min_y) else {
+                continue;
+            };
+            let x = left - min_x;
+            let mut cells = piece.cells(state, x, y);
+            cells.sort();
+            if cells == active {
+                piece.x = x;
+                piece.y = y;
+                piece.current_state = state as u8;
+                return Some(piece);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "..........\nIJLOSZT...\nGGGG.GGGGG\n";
+        let (game_map, piece) = parse(text).unwrap();
+        assert!(piece.is_none());
+        assert_eq!(game_map[1], [1, 2, 3, 4, 5, 6, 7, 0, 0, 0]);
+        assert_eq!(to_text(&game_map, None), text);
+    }
+
+    #[test]
+    fn active_piece_overlay() {
+        let text = "....t.....\n...ttt....\nGGGG.GGGGG\n";
+        let (game_map, piece) = parse(text).unwrap();
+        let piece = piece.unwrap();
+        assert_eq!(letter_from_id(piece.id()), 'T');
+        // The piece is not part of the board.
+        assert!(game_map[..2].iter().flatten().all(|case| *case == 0));
+        assert_eq!(to_text(&game_map, Some(&piece)), text);
+    }
+
+    #[test]
+    fn big_piece_overlay() {
+        let text = "..oooo....\n..oooo....\n..oooo....\n..oooo....\n";
+        let (game_map, piece) = parse(text).unwrap();
+        let piece = piece.unwrap();
+        assert_eq!(piece.scale, 2);
+        assert_eq!(to_text(&game_map, Some(&piece)), text);
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let (game_map, _) = parse("# A well\n\n  ....  \nGGG.\n").unwrap();
+        assert_eq!(game_map, [vec![0; 4], vec![8, 8, 8, 0]]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("....\n...\n").unwrap_err(), "Every row of the board must have the same number of cells");
+        assert_eq!(parse("..X.\n").unwrap_err(), "Unknown cell: X");
+        assert_eq!(parse("# Nothing\n").unwrap_err(), "The board is empty");
+        assert_eq!(parse("tt..\n.tt.\n").unwrap_err(), "The lowercase cells do not form a piece");
+        assert_eq!(parse("ii..\nll..\n").unwrap_err(), "The lowercase cells do not form a piece");
+    }
+}
@@ -0,0 +1,181 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::{
+    display_text,
+    library::{self, Entry, SortKey},
+    replay::replays_dir,
+    viewer, Scene,
+};
+
+const VISIBLE_ROWS: usize = 16;
+
+/// What the typed text is for, if anything.
+#[derive(PartialEq)]
+enum Typing {
+    Nothing,
+    Filter,
+    Rename,
+}
+
+/// Lists the replays of the library until Escape is pressed. Up and Down
+/// select a replay, Enter watches it, Delete twice deletes it and F2 renames
+/// it. Tab changes the sort order, O reverses it and `/` filters by mode or
+/// name.
+pub fn browse(
+    canvas: &mut Canvas<Window>,
+    texture_creator: &TextureCreator<WindowContext>,
+    font: &sdl2::ttf::Font,
+    scene: &Scene,
+    event_pump: &mut sdl2::EventPump,
+) {
+    let mut entries = library::load(&replays_dir());
+    let mut sort_key = SortKey::Date;
+    let mut reverse = false;
+    let mut filter = String::new();
+    let mut typing = Typing::Nothing;
+    let mut typed = String::new();
+    let mut selected = 0;
+    let mut deleting = false;
+    let mut message = String::new();
+
+    loop {
+        library::sort(&mut entries, sort_key, reverse);
+        let shown: Vec<usize> = (0..entries.len()).filter(|i| entries[*i].matches(&filter)).collect();
+        selected = selected.min(shown.len().saturating_sub(1));
+
+        let mut watch = None;
+        for event in event_pump.poll_iter() {
+            if let Event::Quit { .. } = event {
+                return;
+            }
+            if typing != Typing::Nothing {
+                match event {
+                    // The slash opening the filter comes through as text too.
+                    Event::TextInput { text, .. } => typed.extend(text.chars().filter(|letter| *letter != '/')),
+                    Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                        typed.pop();
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                        if typing == Typing::Filter {
+                            filter.clear();
+                        }
+                        typing = Typing::Nothing;
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                        if typing == Typing::Filter {
+                            filter = typed.clone();
+                        } else if let Some(entry) = shown.get(selected).map(|i| &mut entries[*i]) {
+                            message = match entry.rename(&typed) {
+                                Ok(()) => format!("Renamed to {}", entry.name()),
+                                Err(err) => err,
+                            };
+                        }
+                        typing = Typing::Nothing;
+                    }
+                    _ => {}
+                }
+                if typing == Typing::Filter {
+                    filter = typed.clone();
+                }
+                continue;
+            }
+
+            match event {
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return,
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    if keycode != Keycode::Delete {
+                        deleting = false;
+                    }
+                    match keycode {
+                        Keycode::Up => selected = selected.saturating_sub(1),
+                        Keycode::Down => selected = (selected + 1).min(shown.len().saturating_sub(1)),
+                        Keycode::Tab => sort_key = sort_key.next(),
+                        Keycode::O => reverse = !reverse,
+                        Keycode::Return => watch = shown.get(selected).copied(),
+                        Keycode::Slash => {
+                            typing = Typing::Filter;
+                            typed = filter.clone();
+                        }
+                        Keycode::F2 if !shown.is_empty() => {
+                            typing = Typing::Rename;
+                            typed = entries[shown[selected]].name();
+                        }
+                        Keycode::Delete if !shown.is_empty() && deleting => {
+                            let entry = entries.remove(shown[selected]);
+                            message = match entry.delete() {
+                                Ok(()) => format!("Deleted {}", entry.name()),
+                                Err(err) => err,
+                            };
+                            deleting = false;
+                            break;
+                        }
+                        Keycode::Delete if !shown.is_empty() => {
+                            deleting = true;
+                            message = "Press Delete again to confirm".to_owned();
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(index) = watch {
+            viewer::watch(&entries[index].replay, canvas, texture_creator, font, scene, event_pump);
+            continue;
+        }
+
+        draw(canvas, texture_creator, font, &entries, &shown, selected);
+        let order = if reverse { ", reversed" } else { "" };
+        let header = match typing {
+            Typing::Filter => format!("Filter: {}_", typed),
+            Typing::Rename => format!("Rename to: {}_", typed),
+            Typing::Nothing if !filter.is_empty() => format!("Sorted by {}{}, filter: {}", sort_key.name(), order, filter),
+            Typing::Nothing => format!("Sorted by {}{}", sort_key.name(), order),
+        };
+        display_text(canvas, texture_creator, font, &header, 20, 20);
+        if let Some(entry) = shown.get(selected).map(|i| &entries[*i]) {
+            display_text(canvas, texture_creator, font, &entry.name(), 20, 670);
+        }
+        if !message.is_empty() {
+            display_text(canvas, texture_creator, font, &message, 20, 705);
+        }
+        display_text(canvas, texture_creator, font, "Enter watch, Del delete, F2 rename", 20, 740);
+        canvas.present();
+
+        sleep(Duration::from_millis(1000 / 60));
+    }
+}
+
+fn draw(
+    canvas: &mut Canvas<Window>,
+    texture_creator: &TextureCreator<WindowContext>,
+    font: &sdl2::ttf::Font,
+    entries: &[Entry],
+    shown: &[usize],
+    selected: usize,
+) {
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    if shown.is_empty() {
+        display_text(canvas, texture_creator, font, "No replays", 20, 90);
+        return;
+    }
+
+    let first = selected.saturating_sub(VISIBLE_ROWS - 1);
+    for (row, index) in shown.iter().enumerate().skip(first).take(VISIBLE_ROWS) {
+        let y = 70 + (row - first) as i32 * 35;
+        if row == selected {
+            canvas.set_draw_color(Color::RGB(60, 60, 120));
+            canvas.fill_rect(Rect::new(10, y - 2, 580, 34)).expect("Failed to draw selection");
+        }
+        display_text(canvas, texture_creator, font, &entries[*index].summary(), 20, y);
+    }
+}
@@ -1,4 +1,28 @@
 pub const TETRIS_HEIGHT: usize = 40;
 
+pub const FPS: u32 = 60;
+pub const GRAVITY_UNIT: u32 = 65536;
+pub const ENTRY_DELAY: u32 = 6;
+
+pub const GARBAGE: u8 = 8;
+
+pub const LOCK_FLASH: u32 = 10;
+pub const FADE_TIME: u32 = 1000;
+
 pub const LEVEL_TIMES: [u32; 10] = [1000, 850, 700, 600, 500, 400, 300, 250, 221, 190];
 pub const LEVEL_LINES: [u32; 10] = [20,    40,  60,  80, 100, 120, 140, 160, 180, 200];
+
+pub const SPRINT_LINES: u32 = 40;
+pub const ULTRA_SECONDS: u32 = 120;
+pub const MARATHON_LINES: u32 = 150;
+pub const MARATHON_MAX_LEVEL: u32 = 20;
+pub const DIG_LINES: u32 = 18;
+pub const DIG_MESSINESS: u32 = 30;
+pub const DIG_VISIBLE_ROWS: u32 = 8;
+pub const BTYPE_LINES: u32 = 25;
+pub const BTYPE_HEIGHTS: [usize; 6] = [0, 2, 4, 6, 8, 10];
+pub const FADING_SECONDS: u32 = 5;
+pub const UNDO_LIMIT: usize = 1000;
+pub const REWIND_SECONDS: u32 = 5;
+pub const REWIND_HISTORY: u32 = 120;
+pub const AUTOSAVE_SECONDS: u32 = 30;
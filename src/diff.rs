@@ -0,0 +1,154 @@
+use crate::{
+    config::FPS,
+    game::{Lock, Tetris},
+    mode::format_time,
+    replay::{Playback, Replay},
+    tetriminos::letter_from_id,
+    verify::state_hash,
+};
+
+/// How one piece was placed during a replay.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub lock: Lock,
+    /// Tick at which the piece locked.
+    pub frame: u32,
+    /// Inputs made since the previous piece locked.
+    pub inputs: usize,
+}
+
+impl Placement {
+    fn same_spot(&self, other: &Placement) -> bool {
+        (self.lock.piece, self.lock.x, self.lock.y, self.lock.rotation)
+            == (other.lock.piece, other.lock.x, other.lock.y, other.lock.rotation)
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} x{} y{} r{} at {} in {} inputs",
+            letter_from_id(self.lock.piece),
+            self.lock.x,
+            self.lock.y,
+            self.lock.rotation,
+            format_time(millis(self.frame)),
+            self.inputs
+        )
+    }
+}
+
+/// Plays the replay and lists where every piece locked.
+pub fn placements(replay: &Replay) -> Vec<Placement> {
+    let mut playback = Playback::new(replay);
+    let mut placements = Vec::new();
+    let mut previous_frame = 0;
+    loop {
+        let in_play = playback.tetris.current_piece.is_some();
+        if !playback.step() {
+            break;
+        }
+        let tetris = &playback.tetris;
+        if let (true, None, Some(lock)) = (in_play, &tetris.current_piece, tetris.last_lock) {
+            let inputs = replay
+                .inputs
+                .iter()
+                .filter(|(frame, _, _)| (previous_frame..tetris.frame).contains(frame))
+                .count();
+            placements.push(Placement {
+                lock,
+                frame: tetris.frame,
+                inputs,
+            });
+            previous_frame = tetris.frame;
+        }
+    }
+    placements
+}
+
+/// Prints where two replays of the same seed start to differ, then compares
+/// their placements, inputs and timing piece by piece.
+pub fn diff(a: &Replay, b: &Replay) -> Result<(), String> {
+    if (a.mode, a.seed) != (b.mode, b.seed) {
+        return Err("The replays must share the same mode and seed".to_owned());
+    }
+    println!("{} with seed {}", a.mode.name(), a.seed);
+
+    let (mut first, mut second) = (Playback::new(a), Playback::new(b));
+    let mut state_split = None;
+    let mut board_split = None;
+    loop {
+        let (tetris_a, tetris_b) = (&first.tetris, &second.tetris);
+        if state_split.is_none() && state_hash(tetris_a) != state_hash(tetris_b) {
+            state_split = Some(position(tetris_a, tetris_b));
+        }
+        if tetris_a.game_map != tetris_b.game_map {
+            board_split = Some(position(tetris_a, tetris_b));
+            break;
+        }
+        let (stepped_a, stepped_b) = (first.step(), second.step());
+        if !stepped_a && !stepped_b {
+            break;
+        }
+    }
+    match state_split {
+        Some(split) => println!("States differ from {}", split),
+        None => println!("Same state on every tick"),
+    }
+    match board_split {
+        Some(split) => println!("Boards diverge at {}", split),
+        None => println!("Boards never diverge"),
+    }
+
+    let (placements_a, placements_b) = (placements(a), placements(b));
+    let shared = placements_a.len().min(placements_b.len());
+    let mut different = 0;
+    let (mut wasted_a, mut wasted_b) = (0, 0);
+    for (nb, (placement_a, placement_b)) in placements_a.iter().zip(&placements_b).enumerate() {
+        if !placement_a.same_spot(placement_b) {
+            different += 1;
+            if different <= 10 {
+                println!("Piece {}: {} / {}", nb + 1, placement_a.describe(), placement_b.describe());
+            }
+        } else if placement_a.inputs > placement_b.inputs {
+            wasted_a += 1;
+        } else if placement_b.inputs > placement_a.inputs {
+            wasted_b += 1;
+        }
+    }
+    if different > 10 {
+        println!("... and {} more", different - 10);
+    }
+    println!("Placements: {} of {} shared pieces differ", different, shared);
+    println!(
+        "Same placement with more inputs: {} pieces for the first, {} for the second",
+        wasted_a, wasted_b
+    );
+
+    let summary = |replay: &Replay, placements: &[Placement]| {
+        let count = placements.len().max(1) as u32;
+        let inputs: usize = placements.iter().map(|placement| placement.inputs).sum();
+        format!(
+            "{} pieces, {:.2} inputs and {} per piece, {} in total, score {}, {} lines",
+            placements.len(),
+            inputs as f64 / count as f64,
+            format_time(millis(placements.last().map_or(0, |placement| placement.frame)) / count),
+            format_time(millis(replay.length)),
+            replay.score,
+            replay.lines
+        )
+    };
+    println!("First: {}", summary(a, &placements_a));
+    println!("Second: {}", summary(b, &placements_b));
+    Ok(())
+}
+
+fn position(a: &Tetris, b: &Tetris) -> String {
+    if a.nb_pieces == b.nb_pieces {
+        format!("tick {}, piece {}", a.frame, a.nb_pieces)
+    } else {
+        format!("tick {}, pieces {} and {}", a.frame, a.nb_pieces, b.nb_pieces)
+    }
+}
+
+fn millis(frame: u32) -> u32 {
+    (frame as u64 * 1000 / FPS as u64) as u32
+}
@@ -0,0 +1,182 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+use std::fs;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::{
+    config::TETRIS_HEIGHT,
+    display_text,
+    puzzle::Puzzle,
+    tetriminos::{id_from_letter, letter_from_id},
+    Scene,
+};
+
+/// Cell ids offered for painting, the last one erasing.
+const PALETTE: [u8; 9] = [1, 2, 3, 4, 5, 6, 7, 8, 0];
+const PALETTE_X: i32 = 450;
+const PALETTE_Y: i32 = 100;
+const QUEUE_WIDTH: usize = 15;
+
+/// Edits a position until Enter, which returns it to be played, or Escape.
+/// The mouse paints the board with the colour picked in the palette, right
+/// click erasing. Piece letters add to the queue and Backspace removes the
+/// last one, H changes the hold piece, Delete clears the board and E exports
+/// the position to `path` as a puzzle.
+pub fn edit(
+    mut puzzle: Puzzle,
+    path: &Path,
+    canvas: &mut Canvas<Window>,
+    texture_creator: &TextureCreator<WindowContext>,
+    font: &sdl2::ttf::Font,
+    scene: &Scene,
+    event_pump: &mut sdl2::EventPump,
+) -> Option<Puzzle> {
+    let mut colour = 0;
+    let mut message = String::new();
+
+    loop {
+        for event in event_pump.poll_iter() {
+            let painted = match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return None,
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } => return Some(puzzle),
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    match keycode {
+                        Keycode::I | Keycode::J | Keycode::L | Keycode::O | Keycode::S | Keycode::Z | Keycode::T => {
+                            puzzle.pieces.extend(keycode.name().chars().next().and_then(id_from_letter));
+                        }
+                        Keycode::Backspace => {
+                            puzzle.pieces.pop();
+                        }
+                        Keycode::H => {
+                            puzzle.hold = match puzzle.hold {
+                                None => Some(1),
+                                Some(7) => None,
+                                Some(id) => Some(id + 1),
+                            };
+                        }
+                        Keycode::Delete => puzzle.game_map.iter_mut().for_each(|line| line.fill(0)),
+                        Keycode::E => {
+                            message = match export(&puzzle, path) {
+                                Ok(()) => format!("Exported to {}", path.display()),
+                                Err(err) => err,
+                            };
+                        }
+                        _ => {}
+                    }
+                    None
+                }
+                Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+                    if let Some(swatch) = swatch_at(x, y) {
+                        colour = swatch;
+                    }
+                    match mouse_btn {
+                        MouseButton::Left => Some((x, y, PALETTE[colour])),
+                        MouseButton::Right => Some((x, y, 0)),
+                        _ => None,
+                    }
+                }
+                Event::MouseMotion { mousestate, x, y, .. } if mousestate.left() => Some((x, y, PALETTE[colour])),
+                Event::MouseMotion { mousestate, x, y, .. } if mousestate.right() => Some((x, y, 0)),
+                _ => None,
+            };
+            if let Some((x, y, id)) = painted {
+                if let Some((column, row)) = cell_at(&puzzle, scene, x, y) {
+                    puzzle.game_map[row][column] = id;
+                }
+            }
+        }
+
+        draw(canvas, scene, &puzzle, colour);
+        let hold = puzzle.hold.map_or('-', letter_from_id);
+        display_text(canvas, texture_creator, font, &format!("Hold: {}", hold), PALETTE_X, 270);
+        display_text(canvas, texture_creator, font, &format!("Queue: {}", puzzle.pieces.len()), PALETTE_X, 305);
+        for (line_nb, line) in puzzle.pieces.chunks(QUEUE_WIDTH).take(10).enumerate() {
+            let line: String = line.iter().map(|id| letter_from_id(*id)).collect();
+            display_text(canvas, texture_creator, font, &line, PALETTE_X, 340 + line_nb as i32 * 35);
+        }
+        if !message.is_empty() {
+            display_text(canvas, texture_creator, font, &message, 20, 20);
+        }
+        display_text(canvas, texture_creator, font, "Click paint, right click erase, IJLOSZT queue", 20, 730);
+        display_text(canvas, texture_creator, font, "H hold, Del clear, E export, Enter play", 20, 765);
+        canvas.present();
+
+        sleep(Duration::from_millis(1000 / 60));
+    }
+}
+
+fn export(puzzle: &Puzzle, path: &Path) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    fs::write(path, puzzle.to_text()).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// The board cell under the mouse, as column and row.
+fn cell_at(puzzle: &Puzzle, scene: &Scene, x: i32, y: i32) -> Option<(usize, usize)> {
+    if x < scene.grid_x || y < scene.grid_y {
+        return None;
+    }
+    let column = ((x - scene.grid_x) / TETRIS_HEIGHT as i32) as usize;
+    let row = ((y - scene.grid_y) / TETRIS_HEIGHT as i32) as usize;
+    puzzle.game_map.get(row)?.get(column)?;
+    Some((column, row))
+}
+
+fn swatch_rect(swatch: usize) -> Rect {
+    let size = TETRIS_HEIGHT as i32;
+    Rect::new(
+        PALETTE_X + (swatch % 3) as i32 * (size + 10),
+        PALETTE_Y + (swatch / 3) as i32 * (size + 10),
+        size as u32,
+        size as u32,
+    )
+}
+
+fn swatch_at(x: i32, y: i32) -> Option<usize> {
+    (0..PALETTE.len()).find(|swatch| swatch_rect(*swatch).contains_point((x, y)))
+}
+
+/// Texture of a cell id, the last one being black for empty cells.
+fn texture_index(id: u8) -> usize {
+    (id as usize + 8) % 9
+}
+
+fn draw(canvas: &mut Canvas<Window>, scene: &Scene, puzzle: &Puzzle, colour: usize) {
+    let size = TETRIS_HEIGHT as u32;
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas
+        .copy(&scene.border, None, Rect::new(scene.grid_x - 10, scene.grid_y - 10, size * 10 + 20, size * 16 + 20))
+        .expect("Failed to copy border");
+    canvas
+        .copy(&scene.grid, None, Rect::new(scene.grid_x, scene.grid_y, size * 10, size * 16))
+        .expect("Failed to copy grid");
+
+    for (row, line) in puzzle.game_map.iter().enumerate() {
+        for (column, case) in line.iter().enumerate().filter(|(_, case)| **case != 0) {
+            let x = scene.grid_x + column as i32 * size as i32;
+            let y = scene.grid_y + row as i32 * size as i32;
+            canvas
+                .copy(&scene.textures[texture_index(*case)], None, Rect::new(x, y, size, size))
+                .expect("Failed to draw cell");
+        }
+    }
+
+    for (swatch, id) in PALETTE.iter().enumerate() {
+        let rect = swatch_rect(swatch);
+        canvas.copy(&scene.textures[texture_index(*id)], None, rect).expect("Failed to draw palette");
+        let outline = if swatch == colour { Color::RGB(255, 255, 255) } else { Color::RGB(80, 80, 80) };
+        canvas.set_draw_color(outline);
+        canvas
+            .draw_rect(Rect::new(rect.x() - 3, rect.y() - 3, size + 6, size + 6))
+            .expect("Failed to draw palette");
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{game::Input, tetriminos::Tetrimino};
+
+/// A spot where a piece can lock, with the fewest inputs taking it there
+/// from where it starts, the last one being the hard drop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Move {
+    pub x: isize,
+    pub y: usize,
+    pub rotation: u8,
+    pub inputs: Vec<Input>,
+}
+
+/// Every spot the piece can lock at on the board. All the positions reachable
+/// with shifts, soft drops and rotations, kicks included, are searched so
+/// that tucks and spins are found too. Gravity is left out, as if the inputs
+/// all came before the piece falls.
+pub fn find_moves(game_map: &[Vec<u8>], piece: &Tetrimino) -> Vec<Move> {
+    if !piece.test_current_position(game_map) {
+        return Vec::new();
+    }
+    let step = piece.scale as isize;
+    let start = (piece.x, piece.y, piece.current_state);
+    let mut paths = HashMap::from([(start, Vec::new())]);
+    let mut queue = VecDeque::from([start]);
+    let mut locked = HashSet::new();
+    let mut moves = Vec::new();
+
+    // Breadth first, so the first path reaching a position is a shortest one.
+    while let Some(position) = queue.pop_front() {
+        let mut current = piece.clone();
+        (current.x, current.y, current.current_state) = position;
+        let path = paths[&position].clone();
+
+        let mut dropped = current.clone();
+        while dropped.change_position(game_map, dropped.x, dropped.y + 1) {}
+        if locked.insert((dropped.x, dropped.y, dropped.current_state)) {
+            moves.push(Move {
+                x: dropped.x,
+                y: dropped.y,
+                rotation: dropped.current_state,
+                inputs: path.iter().copied().chain([Input::HardDrop]).collect(),
+            });
+        }
+
+        for input in [Input::Left, Input::Right, Input::SoftDrop, Input::Rotate] {
+            let mut next = current.clone();
+            let moved = match input {
+                Input::Left => next.change_position(game_map, next.x - step, next.y),
+                Input::Right => next.change_position(game_map, next.x + step, next.y),
+                Input::SoftDrop => next.change_position(game_map, next.x, next.y + 1),
+                _ => next.rotate(game_map),
+            };
+            let position = (next.x, next.y, next.current_state);
+            if moved && !paths.contains_key(&position) {
+                paths.insert(position, path.iter().copied().chain([input]).collect());
+                queue.push_back(position);
+            }
+        }
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board,
+        game::{Lock, Tetris},
+        mode::GameMode,
+        tetriminos::create_tetrimino,
+    };
+
+    /// The moves of a new `id` piece on a board, bottom aligned, each with
+    /// the lock its inputs lead to once applied to the game.
+    fn moves(id: u8, text: &str) -> Vec<(Move, Lock)> {
+        let (rows, _) = board::parse(text).unwrap();
+        let mut tetris = Tetris::new(GameMode::Zen);
+        let top = tetris.game_map.len() - rows.len();
+        tetris.game_map[top..].clone_from_slice(&rows);
+        tetris.current_piece = Some(create_tetrimino(id));
+
+        let piece = create_tetrimino(id);
+        find_moves(&tetris.game_map, &piece)
+            .into_iter()
+            .map(|found| {
+                let mut game = tetris.clone();
+                for input in &found.inputs {
+                    game.apply(*input);
+                }
+                let lock = game.last_lock.unwrap();
+                assert_eq!((lock.x, lock.y, lock.rotation), (found.x, found.y, found.rotation));
+                (found, lock)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_column_on_an_empty_board() {
+        let found = moves(4, "..........");
+        assert_eq!(found.len(), 9);
+        assert!(found.iter().all(|(found, _)| found.y == 14));
+    }
+
+    #[test]
+    fn tuck_under_an_overhang() {
+        let found = moves(4, ".......GGG\n..........\nGGGG......");
+        let (tuck, _) = found.iter().find(|(found, _)| (found.x, found.y) == (8, 14)).unwrap();
+        // Only reachable sliding in once below the overhang.
+        let last_drop = tuck.inputs.iter().rposition(|input| *input == Input::SoftDrop).unwrap();
+        assert!(tuck.inputs[last_drop..].contains(&Input::Right));
+        assert!(found.iter().any(|(found, _)| (found.x, found.y) == (8, 11)));
+    }
+
+    #[test]
+    fn t_spin_with_a_kick() {
+        let found = moves(7, "...G......\nGGG...GGGG\nGGGG.GGGGG");
+        let (spin, lock) = found
+            .iter()
+            .find(|(found, _)| (found.x, found.y, found.rotation) == (3, 13, 1))
+            .unwrap();
+        // Rotating in place is blocked, the rotation kicks the piece left.
+        assert_eq!(spin.inputs[spin.inputs.len() - 2], Input::Rotate);
+        assert!(lock.t_spin);
+        assert_eq!(lock.lines, 1);
+    }
+
+    #[test]
+    fn t_spin_double_slot() {
+        let found = moves(7, "......GGGG\nGGGG..GGGG\nGGGGG.GGGG");
+        let (_, lock) = found
+            .iter()
+            .find(|(found, _)| (found.x, found.y, found.rotation) == (4, 13, 1))
+            .unwrap();
+        assert_eq!(lock.lines, 2);
+    }
+}
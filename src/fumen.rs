@@ -0,0 +1,504 @@
+use std::fs;
+
+use crate::{board, tetriminos::Tetrimino};
+
+const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const WIDTH: usize = 10;
+/// Rows of a fumen field, not counting the garbage row below them.
+const HEIGHT: usize = 23;
+const BLOCKS: usize = WIDTH * (HEIGHT + 1);
+/// Cell id of each fumen block, which orders the pieces I, L, O, Z, T, J, S.
+/// The engine's J and L have each other's guideline shapes, so they swap.
+const IDS: [u8; 9] = [0, 1, 2, 4, 6, 7, 3, 5, 8];
+/// Blocks of each fumen piece around its centre in the spawn state, y up.
+const SHAPES: [[(isize, isize); 4]; 7] = [
+    [(0, 0), (-1, 0), (1, 0), (2, 0)],
+    [(0, 0), (-1, 0), (1, 0), (1, 1)],
+    [(0, 0), (1, 0), (0, 1), (1, 1)],
+    [(0, 0), (1, 0), (0, 1), (-1, 1)],
+    [(0, 0), (-1, 0), (1, 0), (0, 1)],
+    [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+    [(0, 0), (-1, 0), (0, 1), (1, 1)],
+];
+/// Fumen rotations as encoded: reverse, right, spawn and left.
+const SPAWN: usize = 2;
+
+/// One page of a fumen: the board, with the piece placed on it if any.
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub game_map: Vec<Vec<u8>>,
+    pub piece: Option<Tetrimino>,
+    /// Whether the piece locks and full lines clear going to the next page.
+    pub lock: bool,
+    pub comment: String,
+}
+
+/// A piece as fumen places it: kind from 1 to 7, rotation and centre, with
+/// `y` counted up from the bottom row.
+#[derive(Clone, Copy, PartialEq)]
+struct Operation {
+    kind: usize,
+    rotation: usize,
+    x: isize,
+    y: isize,
+}
+
+impl Operation {
+    fn cells(&self) -> Vec<(isize, isize)> {
+        SHAPES[self.kind - 1]
+            .iter()
+            .map(|&(x, y)| match self.rotation {
+                0 => (-x, -y),
+                1 => (y, -x),
+                SPAWN => (x, y),
+                _ => (-y, x),
+            })
+            .map(|(x, y)| (self.x + x, self.y + y))
+            .collect()
+    }
+
+    /// Old fumen stores some pieces by another block than their centre.
+    fn stored_offset(&self) -> (isize, isize) {
+        match (self.kind, self.rotation) {
+            (3, 3) => (1, -1),
+            (3, 0) | (1, 0) | (4, 3) => (1, 0),
+            (3, SPAWN) | (1, 3) | (7, SPAWN) | (4, SPAWN) => (0, -1),
+            (7, 1) => (-1, 0),
+            _ => (0, 0),
+        }
+    }
+
+    /// The fumen placement covering the same cells as an engine piece.
+    fn from_piece(piece: &Tetrimino, height: usize) -> Option<Operation> {
+        let kind = IDS.iter().position(|id| *id == piece.id())?;
+        let mut cells: Vec<(isize, isize)> = piece
+            .cells(piece.current_state as usize, piece.x, piece.y)
+            .iter()
+            .map(|&(x, y, _)| (x, height as isize - 1 - y as isize))
+            .collect();
+        cells.sort();
+        for rotation in [SPAWN, 1, 0, 3] {
+            // Try each block of the rotated piece on the first cell.
+            for (x, y) in (Operation { kind, rotation, x: 0, y: 0 }).cells() {
+                let operation = Operation {
+                    kind,
+                    rotation,
+                    x: cells[0].0 - x,
+                    y: cells[0].1 - y,
+                };
+                let mut covered = operation.cells();
+                covered.sort();
+                if covered == cells {
+                    return Some(operation);
+                }
+            }
+        }
+        None
+    }
+
+    /// The engine piece on a board of `height` rows.
+    fn to_piece(self, height: usize) -> Result<Tetrimino, String> {
+        let cells = self
+            .cells()
+            .into_iter()
+            .map(|(x, y)| match (height as isize - 1).checked_sub(y) {
+                Some(row) if (0..WIDTH as isize).contains(&x) && row >= 0 => Ok((x, row as usize, IDS[self.kind])),
+                _ => Err("The piece is outside of the board".to_owned()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        board::find_piece(&cells).ok_or_else(|| "Invalid piece".to_owned())
+    }
+}
+
+/// The blocks of a fumen field in encoding order, from the top left to the
+/// garbage row.
+type Field = [u8; BLOCKS];
+
+/// Reads a `v115@` fumen, as copied from fumen editors or their URLs.
+pub fn decode(text: &str) -> Result<Vec<Page>, String> {
+    let (version, data) = text.split_once('@').ok_or("Not a fumen")?;
+    if !["v115", "m115", "d115"].iter().any(|prefix| version.ends_with(prefix)) {
+        return Err("Only version 115 fumen are supported".to_owned());
+    }
+    let mut values = Values {
+        values: data
+            .bytes()
+            .filter(|byte| *byte != b'?' && !byte.is_ascii_whitespace())
+            .map(|byte| TABLE.iter().position(|other| *other == byte).map(|value| value as u32))
+            .collect::<Option<_>>()
+            .ok_or("Invalid fumen character")?,
+        position: 0,
+    };
+
+    let mut pages = Vec::new();
+    let mut field = [0; BLOCKS];
+    let mut repeats = 0;
+    let mut comment = String::new();
+    while values.position < values.values.len() {
+        if repeats > 0 {
+            repeats -= 1;
+        } else {
+            let mut changed = false;
+            let mut index = 0;
+            while index < BLOCKS {
+                let run = values.poll(2)? as usize;
+                let (diff, count) = (run / BLOCKS, run % BLOCKS + 1);
+                if index + count > BLOCKS {
+                    return Err("Invalid field".to_owned());
+                }
+                changed |= diff != 8;
+                for block in &mut field[index..index + count] {
+                    *block = (*block as usize + diff).checked_sub(8).filter(|block| *block < 9).ok_or("Invalid field")? as u8;
+                }
+                index += count;
+            }
+            if !changed {
+                repeats = values.poll(1)?;
+            }
+        }
+
+        let mut action = values.poll(3)? as usize;
+        let mut take = |base: usize| {
+            let value = action % base;
+            action /= base;
+            value
+        };
+        let (kind, rotation, position) = (take(8), take(4), take(BLOCKS));
+        let (rise, mirror, _colour, has_comment, lock) = (take(2), take(2), take(2), take(2), take(2) == 0);
+        if has_comment == 1 {
+            let length = values.poll(2)? as usize;
+            let mut escaped = String::new();
+            for _ in 0..length.div_ceil(4) {
+                let mut chunk = values.poll(5)?;
+                for _ in 0..4 {
+                    escaped.push(char::from((chunk % 96) as u8 + 32));
+                    chunk /= 96;
+                }
+            }
+            escaped.truncate(length);
+            comment = unescape(&escaped);
+        }
+
+        let operation = (1..=7).contains(&kind).then(|| {
+            let mut operation = Operation {
+                kind,
+                rotation,
+                x: (position % WIDTH) as isize,
+                y: (HEIGHT - position / WIDTH) as isize - 1,
+            };
+            let (x, y) = operation.stored_offset();
+            operation.x += x;
+            operation.y += y;
+            operation
+        });
+        let page_nb = pages.len() + 1;
+        let page_error = |err: String| format!("Page {}: {}", page_nb, err);
+        pages.push(Page {
+            game_map: to_game_map(&field).map_err(page_error)?,
+            piece: operation.map(|operation| operation.to_piece(16)).transpose().map_err(page_error)?,
+            lock,
+            comment: comment.clone(),
+        });
+
+        if lock {
+            if let Some(operation) = operation {
+                for (x, y) in operation.cells() {
+                    if (0..WIDTH as isize).contains(&x) && (0..HEIGHT as isize).contains(&y) {
+                        field[(HEIGHT - 1 - y as usize) * WIDTH + x as usize] = kind as u8;
+                    }
+                }
+            }
+            clear_lines(&mut field);
+            if rise == 1 {
+                field.copy_within(WIDTH.., 0);
+                field[HEIGHT * WIDTH..].fill(0);
+            }
+            if mirror == 1 {
+                for line in field[..HEIGHT * WIDTH].chunks_mut(WIDTH) {
+                    line.reverse();
+                }
+            }
+        }
+    }
+    if pages.is_empty() {
+        return Err("The fumen has no pages".to_owned());
+    }
+    Ok(pages)
+}
+
+/// Writes the pages as a `v115@` fumen.
+pub fn encode(pages: &[Page]) -> Result<String, String> {
+    let mut values = Vec::new();
+    let mut previous = [0; BLOCKS];
+    let mut repeat_index = None;
+    let mut comment = "";
+    for (page_nb, page) in pages.iter().enumerate() {
+        let page_error = |err: &str| format!("Page {}: {}", page_nb + 1, err);
+        let height = page.game_map.len();
+        if height > HEIGHT || page.game_map.iter().any(|line| line.len() != WIDTH) {
+            return Err(page_error("The board must be at most 23 lines of 10 cells"));
+        }
+        let mut field = [0; BLOCKS];
+        for (line_nb, line) in page.game_map.iter().enumerate() {
+            for (x, case) in line.iter().enumerate() {
+                let block = IDS.iter().position(|id| id == case).ok_or_else(|| page_error("Unknown cell"))?;
+                field[(HEIGHT - height + line_nb) * WIDTH + x] = block as u8;
+            }
+        }
+
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for (block, previous) in field.iter().zip(&previous) {
+            let diff = *block as usize + 8 - *previous as usize;
+            match runs.last_mut() {
+                Some((last, count)) if *last == diff => *count += 1,
+                _ => runs.push((diff, 1)),
+            }
+        }
+        let changed = runs != [(8, BLOCKS)];
+        match repeat_index {
+            Some(index) if !changed && values[index] < 63 => values[index] += 1,
+            _ => {
+                for (diff, count) in runs {
+                    push(&mut values, diff * BLOCKS + count - 1, 2);
+                }
+                repeat_index = (!changed).then(|| {
+                    values.push(0);
+                    values.len() - 1
+                });
+            }
+        }
+
+        let operation = match &page.piece {
+            Some(piece) => Some(Operation::from_piece(piece, height).ok_or_else(|| page_error("The piece has no fumen equivalent"))?),
+            None => None,
+        };
+        let (kind, rotation, position) = match operation {
+            Some(operation) => {
+                let (x, y) = operation.stored_offset();
+                let (x, y) = (operation.x - x, operation.y - y);
+                (operation.kind, operation.rotation, (HEIGHT as isize - 1 - y) * WIDTH as isize + x)
+            }
+            None => (0, 0, 0),
+        };
+        let flags = [!page.lock, page.comment != comment, page_nb == 0, false, false];
+        let flags = flags.iter().fold(0, |value, flag| value * 2 + *flag as usize);
+        push(&mut values, ((flags * BLOCKS + position as usize) * 4 + rotation) * 8 + kind, 3);
+
+        if page.comment != comment {
+            let escaped = escape(&page.comment);
+            if escaped.len() >= 64 * 64 {
+                return Err(page_error("The comment is too long"));
+            }
+            push(&mut values, escaped.len(), 2);
+            for chunk in escaped.as_bytes().chunks(4) {
+                let chunk = chunk.iter().rev().fold(0, |value, byte| value * 96 + (*byte - 32) as usize);
+                push(&mut values, chunk, 5);
+            }
+            comment = &page.comment;
+        }
+
+        if page.lock {
+            for (x, y) in operation.iter().flat_map(Operation::cells) {
+                field[(HEIGHT - 1 - y as usize) * WIDTH + x as usize] = kind as u8;
+            }
+            clear_lines(&mut field);
+        }
+        previous = field;
+    }
+
+    let data: Vec<u8> = values.iter().map(|value| TABLE[*value]).collect();
+    let mut text = "v115@".to_owned();
+    let split = data.len().min(42);
+    text.push_str(std::str::from_utf8(&data[..split]).unwrap_or_default());
+    for chunk in data[split..].chunks(47) {
+        text.push('?');
+        text.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+    }
+    Ok(text)
+}
+
+/// `tetris fumen <fumen>` prints each page as a text board with its comment
+/// on `#` lines, while `tetris fumen <board files...>` reads such boards
+/// back, one page per file, and prints their fumen.
+pub fn run(args: &[String]) -> Result<(), String> {
+    match args {
+        [] => Err("Usage: tetris fumen <fumen> | <board files...>".to_owned()),
+        [text] if text.contains('@') => {
+            let pages = decode(text)?;
+            for (page_nb, page) in pages.iter().enumerate() {
+                if page_nb > 0 {
+                    println!();
+                }
+                for line in page.comment.lines() {
+                    println!("# {}", line);
+                }
+                print!("{}", board::to_text(&page.game_map, page.piece.as_ref()));
+            }
+            Ok(())
+        }
+        paths => {
+            let pages = paths
+                .iter()
+                .map(|path| {
+                    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+                    let (game_map, piece) = board::parse(&text).map_err(|err| format!("{}: {}", path, err))?;
+                    let comment: Vec<&str> = text
+                        .lines()
+                        .filter_map(|line| line.trim().strip_prefix('#'))
+                        .map(str::trim)
+                        .collect();
+                    Ok(Page {
+                        game_map,
+                        piece,
+                        lock: true,
+                        comment: comment.join("\n"),
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            println!("{}", encode(&pages)?);
+            Ok(())
+        }
+    }
+}
+
+struct Values {
+    values: Vec<u32>,
+    position: usize,
+}
+
+impl Values {
+    /// Reads a number written over `count` characters, lowest digit first.
+    fn poll(&mut self, count: usize) -> Result<u32, String> {
+        let digits = self
+            .values
+            .get(self.position..self.position + count)
+            .ok_or("The fumen is truncated")?;
+        self.position += count;
+        Ok(digits.iter().rev().fold(0, |value, digit| value * 64 + digit))
+    }
+}
+
+fn push(values: &mut Vec<usize>, mut value: usize, count: usize) {
+    for _ in 0..count {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+/// The engine's 16 row board, from the bottom of the field.
+fn to_game_map(field: &Field) -> Result<Vec<Vec<u8>>, String> {
+    let top = (HEIGHT - 16) * WIDTH;
+    if field[..top].iter().chain(&field[HEIGHT * WIDTH..]).any(|block| *block != 0) {
+        return Err("Only the bottom 16 lines of the field can hold blocks".to_owned());
+    }
+    Ok(field[top..HEIGHT * WIDTH]
+        .chunks(WIDTH)
+        .map(|line| line.iter().map(|block| IDS[*block as usize]).collect())
+        .collect())
+}
+
+fn clear_lines(field: &mut Field) {
+    let lines: Vec<&[u8]> = field[..HEIGHT * WIDTH]
+        .chunks(WIDTH)
+        .filter(|line| line.contains(&0))
+        .collect();
+    let mut cleared = [0; BLOCKS];
+    let top = (HEIGHT - lines.len()) * WIDTH;
+    cleared[top..HEIGHT * WIDTH].copy_from_slice(&lines.concat());
+    cleared[HEIGHT * WIDTH..].copy_from_slice(&field[HEIGHT * WIDTH..]);
+    *field = cleared;
+}
+
+/// Comments are stored as JavaScript's `escape` writes them.
+fn escape(text: &str) -> String {
+    text.encode_utf16()
+        .map(|unit| match char::from_u32(unit as u32) {
+            Some(letter) if letter.is_ascii_alphanumeric() || "@*_+-./".contains(letter) => letter.to_string(),
+            _ if unit < 256 => format!("%{:02X}", unit),
+            _ => format!("%u{:04X}", unit),
+        })
+        .collect()
+}
+
+fn unescape(text: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = text;
+    while let Some(letter) = rest.chars().next() {
+        let hex = |digits: &str| u16::from_str_radix(digits, 16).ok();
+        let escaped = match (rest.strip_prefix("%u"), rest.strip_prefix('%')) {
+            (Some(after), _) => after.get(..4).and_then(hex).map(|unit| (unit, 6)),
+            (_, Some(after)) => after.get(..2).and_then(hex).map(|unit| (unit, 3)),
+            _ => None,
+        };
+        match escaped {
+            Some((unit, length)) => {
+                units.push(unit);
+                rest = &rest[length..];
+            }
+            None => {
+                units.extend(letter.encode_utf16(&mut [0; 2]).iter());
+                rest = &rest[letter.len_utf8()..];
+            }
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(text: &str, comment: &str) -> Page {
+        let (rows, piece) = board::parse(text).unwrap();
+        let mut game_map = vec![vec![0; WIDTH]; 16];
+        let top = game_map.len() - rows.len();
+        game_map[top..].clone_from_slice(&rows);
+        Page {
+            game_map,
+            piece: piece.map(|mut piece| {
+                piece.y += top;
+                piece
+            }),
+            lock: true,
+            comment: comment.to_owned(),
+        }
+    }
+
+    fn round_trip(pages: &[Page]) -> String {
+        let text = encode(pages).unwrap();
+        let decoded = decode(&text).unwrap();
+        assert_eq!(decoded.len(), pages.len());
+        for (page, decoded) in pages.iter().zip(&decoded) {
+            assert_eq!(
+                board::to_text(&page.game_map, page.piece.as_ref()),
+                board::to_text(&decoded.game_map, decoded.piece.as_ref())
+            );
+            assert_eq!(page.lock, decoded.lock);
+            assert_eq!(page.comment, decoded.comment);
+        }
+        text
+    }
+
+    #[test]
+    fn empty_page() {
+        assert_eq!(round_trip(&[page("..........", "")]), "v115@vhAAgH");
+    }
+
+    #[test]
+    fn data_of_41_characters() {
+        let text = round_trip(&[page("G...G.GG..\n..G..G.G..\n...G.G.GGG", "")]);
+        assert_eq!(text.len(), "v115@".len() + 41);
+    }
+
+    #[test]
+    fn pages_with_pieces_and_comments() {
+        let pages = [
+            page("....t.....\n...tt.....\nGGG.tGGGGG", "T-spin: setup"),
+            page("..........\nGGGG.GGGGG", "T-spin: setup"),
+            page("iiii......\nGGGG.GGGGG", "héllo ✓ 100%"),
+            page("..........\n..........", ""),
+        ];
+        let text = round_trip(&pages);
+        assert!(text.contains('?'));
+    }
+}
@@ -1,56 +1,409 @@
-use std::time::SystemTime;
-use sdl2::render::{Canvas, Texture};
+use sdl2::pixels::Color;
+use sdl2::render::{BlendMode, Canvas, Texture};
 use sdl2::video::Window;
 use sdl2::rect::Rect;
 
-use crate::{config::{LEVEL_LINES, LEVEL_TIMES, TETRIS_HEIGHT}, tetriminos::*};
+use std::collections::VecDeque;
 
+use crate::{
+    config::{
+        BTYPE_HEIGHTS, BTYPE_LINES, DIG_VISIBLE_ROWS, ENTRY_DELAY, FADE_TIME, FPS, GARBAGE, GRAVITY_UNIT, LEVEL_LINES,
+        LEVEL_TIMES, LOCK_FLASH, MARATHON_MAX_LEVEL, TETRIS_HEIGHT,
+    },
+    master::{self, Section},
+    mission::{Mission, MISSIONS},
+    mode::GameMode,
+    puzzle::Goal,
+    randomizer::Randomizer,
+    tetriminos::*,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    Rotate,
+    Hold,
+}
+
+/// What happened when the last piece locked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lock {
+    pub piece: u8,
+    /// Where the piece locked: top-left corner and rotation state.
+    pub x: isize,
+    pub y: usize,
+    pub rotation: u8,
+    pub lines: u32,
+    pub t_spin: bool,
+    pub perfect_clear: bool,
+}
+
+#[derive(Clone)]
 pub struct Tetris {
+    pub mode: GameMode,
     pub game_map: Vec<Vec<u8>>,
+    /// Tick at which each cell of `game_map` was filled.
+    pub lock_times: Vec<Vec<u32>>,
     pub current_level: u32,
     pub score: u32,
     pub nb_lines: u32,
     pub current_piece: Option<Tetrimino>,
+    pub next_piece: Option<Tetrimino>,
+    /// Fixed pieces to play instead of random ones, as in puzzles.
+    pub sequence: Option<VecDeque<u8>>,
+    pub rng: Randomizer,
+    pub hold_piece: Option<Tetrimino>,
+    pub can_hold: bool,
+    /// Number of pieces brought into play so far.
+    pub nb_pieces: u32,
+    pub initial_rotation: bool,
+    pub initial_hold: bool,
+    /// Number of engine ticks played, at `FPS` ticks per second.
+    pub frame: u32,
+    /// Milliseconds between the last tick and the latest input, so that goals
+    /// reached by an input are timed to the millisecond rather than the tick.
+    pub input_millis: u32,
+    /// Time in milliseconds at which the mode's goal was reached.
+    pub finish_time: Option<u32>,
+    pub game_over: bool,
+    pub last_lock: Option<Lock>,
+    pub last_move_rotation: bool,
+    pub garbage_added: u32,
+    pub garbage_cleared: u32,
+    pub garbage_hole: usize,
+    pub sections: Vec<Section>,
+    pub mission_nb: usize,
+    pub mission_progress: u32,
+    pub mission_start: u32,
+    pub combo: u32,
+    pub soft_rows: u32,
+    /// Gravity accumulated towards the next row, in `GRAVITY_UNIT`s.
+    pub fall: u32,
+    pub lock_frames: u32,
+    pub entry_frames: u32,
 }
 
 impl Tetris {
-    pub fn new() -> Tetris {
+    pub fn new(mode: GameMode) -> Tetris {
+        Tetris::with_seed(mode, rand::random())
+    }
+
+    /// A new game whose pieces and garbage all follow from `seed`.
+    pub fn with_seed(mode: GameMode, seed: u64) -> Tetris {
         let mut game_map = Vec::new();
         for _ in 0..16 {
             game_map.push(vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         }
 
-        Tetris {
+        let lock_times = vec![vec![0; 10]; game_map.len()];
+        let mut rng = Randomizer::new(seed);
+
+        let mut tetris = Tetris {
+            mode,
             game_map,
-            current_level: 1,
+            lock_times,
+            current_level: mode.start_level(),
             score: 0,
             nb_lines: 0,
             current_piece: None,
+            next_piece: Some(create_tetrimino(rng.next_piece())),
+            sequence: None,
+            hold_piece: None,
+            can_hold: true,
+            nb_pieces: 0,
+            initial_rotation: false,
+            initial_hold: false,
+            frame: 0,
+            input_millis: 0,
+            finish_time: None,
+            game_over: false,
+            last_lock: None,
+            last_move_rotation: false,
+            garbage_added: 0,
+            garbage_cleared: 0,
+            garbage_hole: rng.below(10),
+            sections: Vec::new(),
+            rng,
+            mission_nb: 0,
+            mission_progress: 0,
+            mission_start: 0,
+            combo: 1,
+            soft_rows: 0,
+            fall: 0,
+            lock_frames: 0,
+            entry_frames: 0,
+        };
+        tetris.feed_garbage();
+        if let GameMode::BType { height, .. } = mode {
+            tetris.scatter_blocks(BTYPE_HEIGHTS[height as usize]);
+        }
+        tetris
+    }
+
+    /// Fills the bottom `rows` lines with randomly coloured blocks, leaving at
+    /// least one hole per line so that none of them is already complete.
+    fn scatter_blocks(&mut self, rows: usize) {
+        let height = self.game_map.len();
+        for line in self.game_map[height - rows..].iter_mut() {
+            for case in line.iter_mut() {
+                *case = match self.rng.below(2) {
+                    0 => 0,
+                    _ => self.rng.below(7) as u8 + 1,
+                };
+            }
+            let hole = self.rng.below(line.len());
+            line[hole] = 0;
+        }
+    }
+
+    /// Prepares a fresh piece for play, doubled in size in big mode.
+    fn sized(&self, piece: Tetrimino) -> Tetrimino {
+        match self.mode {
+            GameMode::Big => piece.enlarge(self.game_map[0].len()),
+            _ => piece,
+        }
+    }
+
+    /// Replaces the random pieces with a fixed sequence. The game is over
+    /// once it runs out.
+    pub fn set_sequence(&mut self, pieces: &[u8]) {
+        self.sequence = Some(pieces.iter().copied().collect());
+        self.next_piece = self.generate_piece();
+    }
+
+    pub fn pieces_left(&self) -> Option<usize> {
+        self.sequence
+            .as_ref()
+            .map(|sequence| sequence.len() + self.next_piece.is_some() as usize)
+    }
+
+    fn generate_piece(&mut self) -> Option<Tetrimino> {
+        match self.sequence {
+            Some(ref mut sequence) => sequence.pop_front().map(create_tetrimino),
+            None => Some(create_tetrimino(self.rng.next_piece())),
+        }
+    }
+
+    fn take_next_piece(&mut self) -> Option<Tetrimino> {
+        let next = self.generate_piece();
+        std::mem::replace(&mut self.next_piece, next)
+    }
+
+    /// Advances the game by one tick: counts down the entry delay, spawns the
+    /// next piece, then applies gravity and lock delay to the current one.
+    pub fn update(&mut self) {
+        if self.is_finished() || self.game_over {
+            return;
+        }
+        self.frame += 1;
+        self.input_millis = 0;
+        if self.mission().is_some() && self.mission_time_left() == 0 {
+            self.game_over = true;
+            return;
+        }
+
+        if self.current_piece.is_none() {
+            self.entry_frames += 1;
+            if self.entry_frames >= self.entry_delay() && !self.spawn_piece() {
+                self.game_over = true;
+                return;
+            }
+        }
+        if self.current_piece.is_some() {
+            self.apply_gravity();
+        }
+        self.check_goal();
+    }
+
+    /// Applies an input that came `millis` milliseconds after the last tick,
+    /// at most a tick.
+    pub fn apply_at(&mut self, input: Input, millis: u32) {
+        self.input_millis = millis.min(1000 / FPS);
+        self.apply(input);
+    }
+
+    pub fn apply(&mut self, input: Input) {
+        if self.is_finished() || self.game_over {
+            return;
+        }
+        if self.current_piece.is_none() {
+            // Buffered for the next spawn: initial rotation and initial hold.
+            match input {
+                Input::Rotate => self.initial_rotation = true,
+                Input::Hold => self.initial_hold = true,
+                _ => {}
+            }
+            return;
+        }
+
+        let step = self.current_piece.as_ref().map_or(1, |piece| piece.scale as isize);
+        match input {
+            Input::Left => {
+                self.move_piece(-step, 0);
+            }
+            Input::Right => {
+                self.move_piece(step, 0);
+            }
+            Input::SoftDrop => {
+                if self.move_piece(0, 1) {
+                    self.fall = 0;
+                    self.soft_rows += 1;
+                } else {
+                    self.make_permanent();
+                }
+            }
+            Input::HardDrop => {
+                while self.move_piece(0, 1) {}
+                self.make_permanent();
+            }
+            Input::Rotate => {
+                if let Some(ref mut piece) = self.current_piece {
+                    if piece.rotate(&self.game_map) {
+                        self.last_move_rotation = true;
+                    }
+                }
+            }
+            Input::Hold => {
+                if self.hold() {
+                    self.fall = 0;
+                    self.lock_frames = 0;
+                }
+            }
+        }
+    }
+
+    fn move_piece(&mut self, shift_x: isize, shift_y: usize) -> bool {
+        let moved = match self.current_piece {
+            Some(ref mut piece) => {
+                let (x, y) = (piece.x + shift_x, piece.y + shift_y);
+                piece.change_position(&self.game_map, x, y)
+            }
+            None => false,
+        };
+        if moved {
+            self.last_move_rotation = false;
+            if shift_y > 0 {
+                self.lock_frames = 0;
+            }
+        }
+        moved
+    }
+
+    fn apply_gravity(&mut self) {
+        self.fall += self.gravity();
+        let mut rows = self.fall / GRAVITY_UNIT;
+        self.fall %= GRAVITY_UNIT;
+
+        while rows > 0 && self.move_piece(0, 1) {
+            rows -= 1;
+        }
+        if !self.is_grounded() {
+            return;
+        }
+        match self.lock_delay() {
+            // Without a lock delay the piece locks as soon as gravity fails to move it.
+            None => {
+                if rows > 0 {
+                    self.make_permanent();
+                }
+            }
+            Some(delay) => {
+                self.lock_frames += 1;
+                if self.lock_frames >= delay {
+                    self.make_permanent();
+                }
+            }
         }
     }
 
-    pub fn create_new_tetrimino(&self) -> Tetrimino {
-        static mut PREV: u8 = 7;
-        let mut rand_num = rand::random::<u8>() % 7;
-        if unsafe { PREV } == rand_num {
-            rand_num = rand::random::<u8>() % 7;
+    fn is_grounded(&self) -> bool {
+        match self.current_piece {
+            Some(ref piece) => !piece.test_position(&self.game_map, piece.current_state as usize, piece.x, piece.y + 1),
+            None => false,
+        }
+    }
+
+    /// Brings the next piece into play, applying any initial hold or rotation
+    /// buffered during the entry delay. Returns `false` when the piece cannot
+    /// be placed, meaning the game is over.
+    fn spawn_piece(&mut self) -> bool {
+        let mut piece = match self.take_next_piece() {
+            Some(piece) => piece,
+            None => return false,
+        };
+        self.can_hold = true;
+
+        if self.initial_hold {
+            let held = match self.hold_piece.take() {
+                Some(held) => Some(held),
+                None => self.take_next_piece(),
+            };
+            if let Some(held) = held {
+                self.hold_piece = Some(create_tetrimino(piece.id()));
+                self.can_hold = false;
+                piece = held;
+            }
+        }
+        let mut piece = self.sized(piece);
+        if self.initial_rotation {
+            piece.rotate(&self.game_map);
+        }
+        self.initial_hold = false;
+        self.initial_rotation = false;
+
+        if !piece.test_current_position(&self.game_map) {
+            if self.mode != GameMode::Zen {
+                return false;
+            }
+            // Zen mode never tops out, the bottom of the board is trimmed instead.
+            while !piece.test_current_position(&self.game_map) {
+                self.game_map.pop();
+                self.game_map.insert(0, vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+                self.lock_times.pop();
+                self.lock_times.insert(0, vec![0; 10]);
+            }
         }
-        unsafe { PREV = rand_num };
-        match rand_num {
-            0 => TetriminoI::new(),
-            1 => TetriminoJ::new(),
-            2 => TetriminoL::new(),
-            3 => TetriminoO::new(),
-            4 => TetriminoS::new(),
-            5 => TetriminoZ::new(),
-            6 => TetriminoT::new(),
-            _ => unreachable!(),
+        self.current_piece = Some(piece);
+        self.nb_pieces += 1;
+        self.soft_rows = 0;
+        self.last_move_rotation = false;
+        if self.mode == GameMode::Master {
+            self.advance_master_level(false);
         }
+        true
     }
 
-    fn check_lines(&mut self) {
+    pub fn hold(&mut self) -> bool {
+        if !self.can_hold {
+            return false;
+        }
+        let id = match self.current_piece {
+            Some(ref piece) => piece.id(),
+            None => return false,
+        };
+        let incoming = match (&self.hold_piece, &self.next_piece) {
+            (Some(held), _) | (None, Some(held)) => self.sized(create_tetrimino(held.id())),
+            (None, None) => return false,
+        };
+        if !incoming.test_current_position(&self.game_map) {
+            return false;
+        }
+        if self.hold_piece.is_none() {
+            self.take_next_piece();
+        }
+        self.hold_piece = Some(create_tetrimino(id));
+        self.current_piece = Some(incoming);
+        self.can_hold = false;
+        true
+    }
+
+    fn check_lines(&mut self) -> u32 {
         let mut y = 0;
         let mut score_add = 0;
+        let mut cleared = 0;
 
         while y < self.game_map.len() {
             let mut complete = true;
@@ -63,8 +416,13 @@ impl Tetris {
             }
 
             if complete {
+                if self.game_map[y].contains(&GARBAGE) {
+                    self.garbage_cleared += 1;
+                }
                 score_add += self.current_level;
+                cleared += 1;
                 self.game_map.remove(y);
+                self.lock_times.remove(y);
                 y -= 1;
             }
             y += 1;
@@ -73,86 +431,326 @@ impl Tetris {
           // A "tetris"
           score_add += 1000;
         }
-        self.update_score(score_add);
+        match self.mode {
+            GameMode::Master => self.update_master_score(cleared),
+            _ => self.update_score(score_add),
+        }
 
         while self.game_map.len() < 16 {
             self.increase_line();
             self.game_map.insert(0, vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            self.lock_times.insert(0, vec![0; 10]);
+        }
+        self.feed_garbage();
+        cleared
+    }
+
+    /// A T-spin is a T piece whose last move was a rotation, locked with at
+    /// least three of the four corners around its centre filled.
+    fn is_t_spin(&self, piece: &Tetrimino) -> bool {
+        if piece.id() != 7 || piece.scale != 1 || !self.last_move_rotation {
+            return false;
+        }
+        let cells = piece.cells(piece.current_state as usize, piece.x, piece.y);
+        let centre = cells.iter().find(|(x, y, _)| {
+            cells
+                .iter()
+                .filter(|(other_x, other_y, _)| (other_x - x).abs() + (*other_y as isize - *y as isize).abs() == 1)
+                .count()
+                == 3
+        });
+        let (centre_x, centre_y) = match centre {
+            Some((x, y, _)) => (*x, *y as isize),
+            None => return false,
+        };
+
+        let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+            .iter()
+            .filter(|(shift_x, shift_y)| {
+                let (x, y) = (centre_x + shift_x, centre_y + shift_y);
+                y >= 0
+                    && (x < 0
+                        || y as usize >= self.game_map.len()
+                        || x as usize >= self.game_map[y as usize].len()
+                        || self.game_map[y as usize][x as usize] != 0)
+            })
+            .count();
+        corners >= 3
+    }
+
+    /// Tops the garbage back up from below in dig mode so that the same number
+    /// of garbage rows stays visible until the target is nearly reached.
+    fn feed_garbage(&mut self) {
+        let (lines, messiness) = match self.mode {
+            GameMode::Dig { lines, messiness } => (lines, messiness),
+            _ => return,
+        };
+
+        let mut visible = self.game_map.iter().filter(|line| line.contains(&GARBAGE)).count() as u32;
+        while visible < DIG_VISIBLE_ROWS && self.garbage_added < lines {
+            if self.garbage_added > 0 && (self.rng.below(100) as u32) < messiness {
+                self.garbage_hole = (self.garbage_hole + 1 + self.rng.below(9)) % 10;
+            }
+            let mut line = vec![GARBAGE; 10];
+            line[self.garbage_hole] = 0;
+
+            self.game_map.remove(0);
+            self.game_map.push(line);
+            self.lock_times.remove(0);
+            self.lock_times.push(vec![self.frame; 10]);
+            self.garbage_added += 1;
+            visible += 1;
         }
     }
 
     fn increase_line(&mut self) {
       self.nb_lines += 1;
-      if self.nb_lines > LEVEL_LINES[self.current_level as usize - 1] {
+      match self.mode {
+        GameMode::Marathon { start_level, .. } => {
+          self.current_level = (start_level + self.nb_lines / 10).min(MARATHON_MAX_LEVEL);
+        }
+        GameMode::Master => self.advance_master_level(true),
+        GameMode::BType { .. } | GameMode::Zen => {}
+        _ => {
+          if let Some(lines) = LEVEL_LINES.get(self.current_level as usize - 1) {
+            if self.nb_lines > *lines && (self.current_level as usize) < LEVEL_TIMES.len() {
+              self.current_level += 1;
+            }
+          }
+        }
+      }
+    }
+
+    /// Master levels go up by one for every piece and every line, but only a
+    /// line clear can get past a section stop at x99 or the final 998.
+    fn advance_master_level(&mut self, line_clear: bool) {
+        let level = self.current_level;
+        if level >= master::MAX_LEVEL || (!line_clear && (level % 100 == 99 || level == master::MAX_LEVEL - 1)) {
+            return;
+        }
         self.current_level += 1;
+        if self.current_level.is_multiple_of(100) || self.current_level == master::MAX_LEVEL {
+            self.sections.push(Section {
+                time: self.elapsed_millis(),
+                score: self.score,
+            });
+        }
+    }
+
+    fn update_master_score(&mut self, lines: u32) {
+        if lines == 0 {
+            self.combo = 1;
+            return;
+        }
+        self.combo += 2 * lines - 2;
+        let bravo = if self.game_map.iter().all(|line| line.iter().all(|case| *case == 0)) { 4 } else { 1 };
+        let score = ((self.current_level + lines).div_ceil(4) + self.soft_rows) * lines * self.combo * bravo;
+        self.update_score(score);
+    }
+
+    /// Milliseconds the current piece takes to fall one row.
+    pub fn level_time(&self) -> u32 {
+      match self.mode {
+        GameMode::Marathon { .. } => {
+          // Guideline gravity curve: (0.8 - (level - 1) * 0.007)^(level - 1) seconds.
+          let level = self.current_level as f64 - 1.0;
+          ((0.8 - level * 0.007).powf(level) * 1000.0) as u32
+        }
+        _ => LEVEL_TIMES[self.current_level as usize - 1],
       }
     }
 
+    /// Rows fallen per tick, in `GRAVITY_UNIT`s.
+    pub fn gravity(&self) -> u32 {
+        match self.mode {
+            GameMode::Master => master::gravity(self.current_level),
+            GameMode::Zen => 0,
+            _ => GRAVITY_UNIT * 1000 / (FPS * self.level_time().max(1)),
+        }
+    }
+
+    /// Ticks between a piece locking and the next one appearing.
+    pub fn entry_delay(&self) -> u32 {
+        match self.mode {
+            GameMode::Master => master::delays(self.current_level).0,
+            _ => ENTRY_DELAY,
+        }
+    }
+
+    /// Ticks a grounded piece waits before locking, or `None` to lock as soon
+    /// as gravity can no longer move it down.
+    pub fn lock_delay(&self) -> Option<u32> {
+        match self.mode {
+            GameMode::Master => Some(master::delays(self.current_level).1),
+            _ => None,
+        }
+    }
+
     pub fn make_permanent(&mut self) {
       let mut to_add = 0;
-        if let Some(ref mut piece) = self.current_piece {
-            let mut shift_y = 0;
-
-            while shift_y < piece.states[piece.current_state as usize].len()
-                && piece.y + shift_y < self.game_map.len()
-            {
-                let mut shift_x = 0;
-
-                while shift_x < piece.states[piece.current_state as usize][shift_y].len()
-                    && (piece.x + shift_x as isize)
-                        < self.game_map[piece.y + shift_y].len() as isize
-                {
-                    if piece.states[piece.current_state as usize][shift_y][shift_x] != 0 {
-                        let x = piece.x + shift_x as isize;
-                        self.game_map[piece.y + shift_y][x as usize] =
-                            piece.states[piece.current_state as usize][shift_y][shift_x];
-                    }
-                    shift_x += 1;
-                }
-                shift_y += 1;
+      let mut lock = None;
+        if let Some(ref piece) = self.current_piece {
+            lock = Some(Lock {
+                piece: piece.id(),
+                x: piece.x,
+                y: piece.y,
+                rotation: piece.current_state,
+                lines: 0,
+                t_spin: self.is_t_spin(piece),
+                perfect_clear: false,
+            });
+            for (x, y, case) in piece.cells(piece.current_state as usize, piece.x, piece.y) {
+                self.game_map[y][x as usize] = case;
+                self.lock_times[y][x as usize] = self.frame;
+            }
+            if self.mode != GameMode::Master {
+                to_add += self.current_level
             }
-            to_add += self.current_level
         }
 
         self.update_score(to_add);
-        self.check_lines();
+        let lines = self.check_lines();
+        self.last_lock = lock.map(|lock| Lock {
+            lines,
+            perfect_clear: lines > 0 && self.game_map.iter().all(|line| line.iter().all(|case| *case == 0)),
+            ..lock
+        });
+        self.check_mission();
+        self.check_goal();
         self.current_piece = None;
+        self.fall = 0;
+        self.lock_frames = 0;
+        self.entry_frames = 0;
+    }
+
+    pub fn mission(&self) -> Option<&'static Mission> {
+        match self.mode {
+            GameMode::Mission => MISSIONS.get(self.mission_nb),
+            _ => None,
+        }
+    }
+
+    /// Milliseconds left to complete the current mission.
+    pub fn mission_time_left(&self) -> u32 {
+        let limit = self.mission().map_or(0, |mission| mission.time_limit * 1000);
+        let spent = ((self.frame - self.mission_start) as u64 * 1000 / FPS as u64) as u32;
+        limit.saturating_sub(spent)
+    }
+
+    fn check_mission(&mut self) {
+        let (mission, lock) = match (self.mission(), self.last_lock) {
+            (Some(mission), Some(lock)) => (mission, lock),
+            _ => return,
+        };
+        if !mission.is_completed_by(&lock) {
+            return;
+        }
+        self.mission_progress += 1;
+        if self.mission_progress >= mission.count {
+            self.mission_nb += 1;
+            self.mission_progress = 0;
+            self.mission_start = self.frame;
+        }
+    }
+
+    /// Ends the game once the mode's goal is reached. Called after every lock
+    /// and on every tick for the modes that run against the clock.
+    pub fn check_goal(&mut self) {
+        if self.finish_time.is_some() {
+            return;
+        }
+        let elapsed = self.elapsed_millis();
+        self.finish_time = match self.mode {
+            GameMode::Endless | GameMode::Fading { .. } | GameMode::Invisible | GameMode::Big | GameMode::Zen => None,
+            GameMode::Sprint { lines } => (self.nb_lines >= lines).then_some(elapsed),
+            GameMode::Ultra { duration } => (elapsed >= duration).then_some(duration),
+            GameMode::Marathon { lines, .. } => lines
+                .filter(|lines| self.nb_lines >= *lines)
+                .map(|_| elapsed),
+            GameMode::Dig { lines, .. } => (self.garbage_cleared >= lines).then_some(elapsed),
+            GameMode::BType { .. } => (self.nb_lines >= BTYPE_LINES).then_some(elapsed),
+            GameMode::Master => (self.current_level >= master::MAX_LEVEL).then_some(elapsed),
+            GameMode::Mission => (self.mission_nb >= MISSIONS.len()).then_some(elapsed),
+            GameMode::Puzzle { goal } => {
+                let reached = match (goal, self.last_lock) {
+                    (Goal::Lines(lines), _) => self.nb_lines >= lines,
+                    (Goal::PerfectClear, Some(lock)) => lock.perfect_clear,
+                    (Goal::TSpinDouble, Some(lock)) => lock.t_spin && lock.lines == 2,
+                    (_, None) => false,
+                };
+                reached.then_some(elapsed)
+            }
+        };
+
+        if let (Some(_), GameMode::BType { height, .. }) = (self.finish_time, self.mode) {
+            self.update_score(1000 * (self.current_level + height));
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finish_time.is_some()
+    }
+
+    pub fn elapsed_millis(&self) -> u32 {
+        match self.finish_time {
+            Some(finish_time) => finish_time,
+            None => (self.frame as u64 * 1000 / FPS as u64) as u32 + self.input_millis,
+        }
+    }
+
+    pub fn grade(&self) -> &'static str {
+        master::grade(self.score, &self.sections)
     }
 
     pub fn update_score(&mut self, to_add: u32) {
       self.score += to_add;
     }
 
-    pub fn is_time_over(&self, timer: &mut SystemTime) -> bool {
-      match timer.elapsed() {
-        Ok(elapsed) => {
-          let millis = elapsed.as_millis() as u32;
-          millis > LEVEL_TIMES[self.current_level as usize - 1]
+    /// How visible a locked cell is, from 0 (hidden) to 255. Everything is
+    /// revealed once the game has ended.
+    pub fn visibility(&self, x: usize, y: usize) -> u8 {
+        if self.game_over || self.is_finished() {
+            return 255;
         }
-        Err(_) => false,
-      }
+        let age = (self.frame - self.lock_times[y][x]) * 1000 / FPS;
+        match self.mode {
+            GameMode::Invisible => 0,
+            GameMode::Fading { delay } if age >= delay + FADE_TIME => 0,
+            GameMode::Fading { delay } if age > delay => (255 - (age - delay) * 255 / FADE_TIME) as u8,
+            _ => 255,
+        }
+    }
+
+    pub fn is_flashing(&self, x: usize, y: usize) -> bool {
+        self.frame - self.lock_times[y][x] < LOCK_FLASH
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>, textures: &[Texture<'_>; 8], grid_x: i32, grid_y: i32) {
+    pub fn draw(&self, canvas: &mut Canvas<Window>, textures: &[Texture<'_>; 9], grid_x: i32, grid_y: i32) {
         for (line_nb, line) in self.game_map.iter().enumerate() {
             for (case_nb, case) in line.iter().enumerate() {
                 if *case == 0 {
                     continue
                 }
 
-                canvas.copy(
-                    &textures[*case as usize - 1],
-                    None,
-                    Rect::new(
-                        grid_x + case_nb as i32 * TETRIS_HEIGHT as i32,
-                        grid_y + line_nb as i32 * TETRIS_HEIGHT as i32,
-                        TETRIS_HEIGHT as u32,
-                        TETRIS_HEIGHT as u32,
-                    )
-                ).expect("Failed to draw grid");
+                let cell = Rect::new(
+                    grid_x + case_nb as i32 * TETRIS_HEIGHT as i32,
+                    grid_y + line_nb as i32 * TETRIS_HEIGHT as i32,
+                    TETRIS_HEIGHT as u32,
+                    TETRIS_HEIGHT as u32,
+                );
+                let visibility = self.visibility(case_nb, line_nb);
+                if visibility == 0 {
+                    if self.is_flashing(case_nb, line_nb) {
+                        canvas.set_draw_color(Color::RGB(255, 255, 255));
+                        canvas.draw_rect(cell).expect("Failed to draw outline");
+                    }
+                    continue
+                }
+
+                canvas.copy(&textures[*case as usize - 1], None, cell).expect("Failed to draw grid");
 
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
                         grid_x + case_nb as i32 * TETRIS_HEIGHT as i32,
@@ -162,7 +760,7 @@ impl Tetris {
                     )
                 ).expect("Failed to draw piece");
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
                         grid_x + case_nb as i32 * TETRIS_HEIGHT as i32,
@@ -173,7 +771,7 @@ impl Tetris {
                 ).expect("Failed to draw piece");
         
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
                         grid_x + case_nb as i32 * TETRIS_HEIGHT as i32,
@@ -183,7 +781,7 @@ impl Tetris {
                     )
                 ).expect("Failed to draw piece");
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
                         grid_x + case_nb as i32 * TETRIS_HEIGHT as i32 + (TETRIS_HEIGHT as i32 - 2),
@@ -192,6 +790,12 @@ impl Tetris {
                         TETRIS_HEIGHT as u32,
                     )
                 ).expect("Failed to draw piece");
+
+                if visibility < 255 {
+                    canvas.set_blend_mode(BlendMode::Blend);
+                    canvas.set_draw_color(Color::RGBA(0, 0, 0, 255 - visibility));
+                    canvas.fill_rect(cell).expect("Failed to fade piece");
+                }
             }
         }
     }
@@ -0,0 +1,99 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+
+use crate::{
+    config::FPS,
+    display_text,
+    game::Tetris,
+    mode::GameMode,
+    replay::{Playback, Replay},
+};
+
+/// A replay played in lockstep with the live game to race against it.
+pub struct Ghost<'a> {
+    playback: Playback<'a>,
+    /// Tick at which the ghost reached each number of lines.
+    splits: Vec<u32>,
+    /// Tick at which the live game reached each number of lines.
+    live_splits: Vec<u32>,
+}
+
+impl<'a> Ghost<'a> {
+    pub fn new(replay: &'a Replay) -> Ghost<'a> {
+        let mut playback = Playback::new(replay);
+        let mut splits = vec![0];
+        while playback.step() {
+            record_split(&mut splits, &playback.tetris);
+        }
+        playback.restart();
+        Ghost {
+            playback,
+            splits,
+            live_splits: vec![0],
+        }
+    }
+
+    /// Advances the ghost by one tick, along with the live game.
+    pub fn step(&mut self, live: &Tetris) {
+        self.playback.step();
+        record_split(&mut self.live_splits, live);
+    }
+
+    pub fn tetris(&self) -> &Tetris {
+        &self.playback.tetris
+    }
+
+    /// Lines ahead of the ghost, negative when behind.
+    pub fn line_delta(&self, live: &Tetris) -> i64 {
+        live.nb_lines as i64 - self.tetris().nb_lines as i64
+    }
+
+    /// Milliseconds behind the ghost when reaching the live game's current
+    /// number of lines, negative when ahead.
+    pub fn time_delta(&self, live: &Tetris) -> Option<i64> {
+        let lines = live.nb_lines as usize;
+        let (live_split, split) = (self.live_splits.get(lines)?, self.splits.get(lines)?);
+        Some((*live_split as i64 - *split as i64) * 1000 / FPS as i64)
+    }
+
+    /// Progress bars for both games under the HUD, the ghost's translucent,
+    /// with the line and time deltas.
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        texture_creator: &TextureCreator<WindowContext>,
+        font: &sdl2::ttf::Font,
+        live: &Tetris,
+        x: i32,
+    ) {
+        let target = match live.mode {
+            GameMode::Sprint { lines } => lines.max(1),
+            _ => return,
+        };
+        let bar = |lines: u32| (lines.min(target) * 160 / target).max(1);
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(255, 255, 255, 255));
+        canvas.fill_rect(Rect::new(x, 240, bar(live.nb_lines), 10)).expect("Failed to draw progress");
+        canvas.set_draw_color(Color::RGBA(255, 255, 255, 96));
+        canvas.fill_rect(Rect::new(x, 255, bar(self.tetris().nb_lines), 10)).expect("Failed to draw progress");
+
+        let line_delta = self.line_delta(live);
+        let lines_text = format!("Ghost: {:+} lines", line_delta);
+        display_text(canvas, texture_creator, font, &lines_text, x, 275);
+        if let Some(time_delta) = self.time_delta(live) {
+            let sign = if time_delta < 0 { '-' } else { '+' };
+            let time_delta = time_delta.unsigned_abs();
+            let time_text = format!("Split: {}{}.{:03}", sign, time_delta / 1000, time_delta % 1000);
+            display_text(canvas, texture_creator, font, &time_text, x, 310);
+        }
+    }
+}
+
+fn record_split(splits: &mut Vec<u32>, tetris: &Tetris) {
+    while splits.len() <= tetris.nb_lines as usize {
+        splits.push(tetris.frame);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{
+    config::{FPS, REWIND_HISTORY, UNDO_LIMIT},
+    game::Tetris,
+};
+
+/// Snapshots of a practice game to undo placements and rewind time with.
+pub struct History {
+    /// The game as each piece came into play, oldest first.
+    placements: Vec<Tetris>,
+    /// The game once every second, oldest first.
+    timeline: Vec<Tetris>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            placements: Vec::new(),
+            timeline: Vec::new(),
+        }
+    }
+
+    /// Called after every tick to take the snapshots that are due.
+    pub fn record(&mut self, tetris: &Tetris) {
+        let new_piece = tetris.current_piece.is_some()
+            && self.placements.last().is_none_or(|last| last.nb_pieces != tetris.nb_pieces);
+        if new_piece {
+            if self.placements.len() >= UNDO_LIMIT {
+                self.placements.remove(0);
+            }
+            self.placements.push(tetris.clone());
+        }
+
+        if tetris.frame.is_multiple_of(FPS) && self.timeline.last().is_none_or(|last| last.frame != tetris.frame) {
+            if self.timeline.len() >= REWIND_HISTORY as usize {
+                self.timeline.remove(0);
+            }
+            self.timeline.push(tetris.clone());
+        }
+    }
+
+    /// The game as it was before the last piece was placed. A piece still in
+    /// play is dropped as well, so undoing always takes one placement back.
+    pub fn undo(&mut self, tetris: &Tetris) -> Option<Tetris> {
+        let in_play = tetris.current_piece.is_some()
+            && self.placements.last().is_some_and(|last| last.nb_pieces == tetris.nb_pieces);
+        let index = self.placements.len().checked_sub(1 + in_play as usize)?;
+        let previous = self.placements[index].clone();
+        self.forget_after(previous.frame);
+        Some(previous)
+    }
+
+    /// The game as it was at least `seconds` seconds ago, or as far back as
+    /// the history goes.
+    pub fn rewind(&mut self, tetris: &Tetris, seconds: u32) -> Option<Tetris> {
+        let target = tetris.frame.saturating_sub(seconds * FPS);
+        let previous = self
+            .timeline
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.frame <= target)
+            .or(self.timeline.first())?
+            .clone();
+        self.forget_after(previous.frame);
+        Some(previous)
+    }
+
+    /// Drops the snapshots taken after `frame`, they belong to a future that
+    /// is about to be replayed differently.
+    fn forget_after(&mut self, frame: u32) {
+        self.placements.retain(|snapshot| snapshot.frame <= frame);
+        self.timeline.retain(|snapshot| snapshot.frame <= frame);
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    config::FPS,
+    mode::{format_time, GameMode},
+    replay::{replays_dir, Replay},
+};
+
+/// A replay file of the library, already read.
+pub struct Entry {
+    pub path: PathBuf,
+    pub replay: Replay,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    Date,
+    Mode,
+    Score,
+    Lines,
+    Duration,
+}
+
+impl SortKey {
+    pub fn next(self) -> SortKey {
+        match self {
+            SortKey::Date => SortKey::Mode,
+            SortKey::Mode => SortKey::Score,
+            SortKey::Score => SortKey::Lines,
+            SortKey::Lines => SortKey::Duration,
+            SortKey::Duration => SortKey::Date,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Date => "date",
+            SortKey::Mode => "mode",
+            SortKey::Score => "score",
+            SortKey::Lines => "lines",
+            SortKey::Duration => "duration",
+        }
+    }
+}
+
+impl Entry {
+    pub fn name(&self) -> String {
+        self.path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+    }
+
+    pub fn duration(&self) -> u32 {
+        (self.replay.length as u64 * 1000 / FPS as u64) as u32
+    }
+
+    /// One line of the replay list: mode, date, score, lines and duration.
+    pub fn summary(&self) -> String {
+        format!(
+            "{:<9} {} {:>7} {:>4} {:>9}",
+            self.replay.mode.name(),
+            format_date(self.replay.date),
+            self.replay.score,
+            self.replay.lines,
+            format_time(self.duration()),
+        )
+    }
+
+    pub fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        self.replay.mode.name().to_lowercase().contains(&filter) || self.name().to_lowercase().contains(&filter)
+    }
+
+    pub fn delete(&self) -> Result<(), String> {
+        fs::remove_file(&self.path).map_err(|err| format!("{}: {}", self.path.display(), err))
+    }
+
+    /// Renames the file in place, keeping its extension.
+    pub fn rename(&mut self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() || name.contains(['/', '\\']) {
+            return Err(format!("Invalid name: {}", name));
+        }
+        let path = self.path.with_file_name(format!("{}.txt", name));
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()));
+        }
+        fs::rename(&self.path, &path).map_err(|err| format!("{}: {}", self.path.display(), err))?;
+        self.path = path;
+        Ok(())
+    }
+}
+
+/// Reads every replay of `dir`, skipping the files that aren't replays.
+pub fn load(dir: &Path) -> Vec<Entry> {
+    let paths = match fs::read_dir(dir) {
+        Ok(paths) => paths,
+        Err(_) => return Vec::new(),
+    };
+    paths
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .filter_map(|path| Replay::read(&path).ok().map(|replay| Entry { path, replay }))
+        .collect()
+}
+
+/// The fastest completed sprint of the library with the same target.
+pub fn personal_best(mode: GameMode) -> Option<Replay> {
+    let lines = match mode {
+        GameMode::Sprint { lines } => lines,
+        _ => return None,
+    };
+    load(&replays_dir())
+        .into_iter()
+        .map(|entry| entry.replay)
+        .filter(|replay| replay.mode == mode && replay.lines >= lines)
+        .min_by_key(|replay| replay.length)
+}
+
+/// Sorts the entries, best or newest first unless `reverse` is set.
+pub fn sort(entries: &mut [Entry], key: SortKey, reverse: bool) {
+    entries.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Date => b.replay.date.cmp(&a.replay.date),
+            SortKey::Mode => a.replay.mode.name().cmp(b.replay.mode.name()),
+            SortKey::Score => b.replay.score.cmp(&a.replay.score),
+            SortKey::Lines => b.replay.lines.cmp(&a.replay.lines),
+            SortKey::Duration => a.replay.length.cmp(&b.replay.length),
+        };
+        if reverse {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+}
+
+/// `YYYY-MM-DD HH:MM` in UTC from seconds since the Unix epoch.
+pub fn format_date(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let minutes = seconds % 86400 / 60;
+
+    // Civil date from a day number, as in Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
+}
@@ -1,24 +1,156 @@
 extern crate sdl2;
 extern crate rand;
 
-use game::Tetris;
+use game::{Input, Tetris};
+use ghost::Ghost;
+use history::History;
+use mission::MISSIONS;
+use mode::{format_time, GameMode};
+use puzzle::Puzzle;
+use replay::Replay;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Scancode};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::{Canvas, Texture, TextureCreator};
 use sdl2::video::{Window, WindowContext};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::thread::sleep;
 use std::time::{Duration, SystemTime};
 
-use crate::config::TETRIS_HEIGHT;
+use crate::config::{AUTOSAVE_SECONDS, BTYPE_LINES, FPS, REWIND_SECONDS, TETRIS_HEIGHT};
 
 mod tetriminos;
+mod board;
+mod browser;
 mod game;
+mod ghost;
+mod history;
+mod library;
 mod config;
+mod diff;
+mod editor;
+mod finder;
+mod fumen;
+mod master;
+mod mission;
+mod mode;
+mod puzzle;
+mod randomizer;
+mod replay;
+mod save;
+mod sim;
+mod verify;
+mod viewer;
 
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let (Some("diff"), Some(first), Some(second)) = (args.first().map(String::as_str), args.get(1), args.get(2)) {
+        let result = Replay::read(Path::new(first))
+            .and_then(|first| Replay::read(Path::new(second)).map(|second| (first, second)))
+            .and_then(|(first, second)| diff::diff(&first, &second));
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.first().is_some_and(|arg| arg == "fumen") {
+        if let Err(err) = fumen::run(&args[1..]) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.first().is_some_and(|arg| arg == "sim") {
+        if let Err(err) = sim::run(&args[1..]) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.first().is_some_and(|arg| arg == "verify") {
+        // Runs without opening a window, for checking leaderboard entries.
+        match verify_command(&args[1..]) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(2);
+            }
+        }
+    }
+    let puzzles = match args.first().map(String::as_str) {
+        Some("puzzle") => Puzzle::load_pack(Path::new(args.get(1).map_or("puzzles", String::as_str))),
+        _ => Ok(Vec::new()),
+    };
+    let puzzles = puzzles.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    let mut puzzle_nb = 0;
+
+    let resumed = match args.first().map(String::as_str) {
+        Some("resume") => match save::read(&args.get(1).map_or_else(save::autosave_path, PathBuf::from)) {
+            Ok(tetris) => Some(tetris),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    let watched = match (args.first().map(String::as_str), args.get(1)) {
+        (Some("replay"), Some(path)) => match Replay::read(Path::new(path)) {
+            Ok(replay) => Some(replay),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    // The position to edit, from the given puzzle file if it exists.
+    let edited = match args.first().map(String::as_str) {
+        Some("edit") => {
+            let path = args.get(1).map_or_else(|| save::data_dir().join("position.txt"), PathBuf::from);
+            let position = if path.exists() {
+                Puzzle::load(&path)
+            } else {
+                let name = path.file_stem().map_or("position".into(), |stem| stem.to_string_lossy());
+                Puzzle::parse(&name, "")
+            };
+            match position {
+                Ok(position) => Some((position, path)),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let mode = match (puzzles.first(), &resumed, &watched) {
+        (Some(puzzle), _, _) => Ok(GameMode::Puzzle { goal: puzzle.goal }),
+        (None, Some(tetris), _) => Ok(tetris.mode),
+        (None, None, Some(replay)) => Ok(replay.mode),
+        // The replay browser doesn't start a game, the editor plays endless.
+        (None, None, None) if args.first().is_some_and(|arg| arg == "replays" || arg == "edit") => Ok(GameMode::Endless),
+        (None, None, None) => GameMode::from_args(args.iter().cloned()),
+    };
+    let mode = match mode {
+        Ok(mode) => mode,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
     let sdl_context = sdl2::init().expect("SDL init failed");
     let video_subsystem = sdl_context.video().expect("SDL video failed");
 
@@ -75,6 +207,14 @@ fn main() {
         TETRIS_HEIGHT as u32 * 4,
     ).expect("Failed to create preview");
 
+    let hold = create_texture_rect(
+        &mut canvas,
+        &texture_creator,
+        Color::RGB(0, 0, 0),
+        TETRIS_HEIGHT as u32 * 4,
+        TETRIS_HEIGHT as u32 * 2,
+    ).expect("Failed to create hold");
+
     macro_rules! texture {
         ($r:expr, $g:expr, $b:expr) => (
             create_texture_rect(
@@ -95,94 +235,277 @@ fn main() {
         texture!(77, 149, 239),
         texture!(39, 218, 225),
         texture!(45, 216, 47),
+        texture!(128, 128, 128),
         texture!(0, 0, 0),
     ];
-    
-    let mut tetris = Tetris::new();
+    let scene = Scene { grid, border, preview, hold, textures, height, grid_x, grid_y };
+
+    if args.first().is_some_and(|arg| arg == "replays") {
+        browser::browse(&mut canvas, &texture_creator, &font, &scene, &mut event_pump);
+        return;
+    }
+    if let Some(replay) = watched {
+        viewer::watch(&replay, &mut canvas, &texture_creator, &font, &scene, &mut event_pump);
+        print_game_information(&replay.play());
+        return;
+    }
+    let edited = match edited {
+        Some((position, path)) => match editor::edit(position, &path, &mut canvas, &texture_creator, &font, &scene, &mut event_pump) {
+            Some(position) => Some(position),
+            None => return,
+        },
+        None => None,
+    };
+
+    // Puzzles, resumed games and edited positions don't start from a seed and
+    // can't be replayed.
+    let seed = rand::random();
+    let mut replay = (puzzles.is_empty() && resumed.is_none() && edited.is_none()).then(|| Replay::new(mode, seed));
+    let mut tetris = match (puzzles.first(), resumed, &edited) {
+        (Some(puzzle), _, _) => puzzle.start(),
+        (None, Some(tetris), _) => tetris,
+        (None, None, Some(position)) => position.play(),
+        (None, None, None) => Tetris::with_seed(mode, seed),
+    };
+    // Sprints race the personal best, simulated tick by tick next to the game.
+    let best = match mode {
+        GameMode::Sprint { .. } if replay.is_some() => library::personal_best(mode),
+        _ => None,
+    };
+    let mut ghost = best.as_ref().map(Ghost::new);
+    let mut history = History::new();
     let mut timer = SystemTime::now();
-    let mut next_piece = tetris.create_new_tetrimino();
+    let mut lag = Duration::ZERO;
+    let frame_duration = Duration::from_secs(1) / FPS;
 
     loop {
-        if tetris.is_time_over(&mut timer) {
-            let mut make_permanent = false;
+        let mut quit = false;
+        let mut retry = false;
+        let mut undo = false;
+        let mut rewind = false;
+        let mut inputs = Vec::new();
+        handle_events(&mut inputs, &mut quit, &mut retry, &mut undo, &mut rewind, &mut event_pump);
+        // Time since the last tick, for finish times to the millisecond.
+        let millis = (lag + timer.elapsed().unwrap_or_default()).as_millis() as u32;
+        for input in inputs {
+            apply_input(&mut tetris, &mut replay, input, millis);
+        }
+        if let (true, Some(puzzle)) = (retry, puzzles.get(puzzle_nb)) {
+            tetris = puzzle.start();
+        }
+        if tetris.mode == GameMode::Zen {
+            let previous = match (undo, rewind) {
+                (true, _) => history.undo(&tetris),
+                (_, true) => history.rewind(&tetris, REWIND_SECONDS),
+                _ => None,
+            };
+            if let Some(previous) = previous {
+                if let Some(ref mut replay) = replay {
+                    replay.truncate(previous.frame);
+                }
+                tetris = previous;
+            }
+        }
+
+        // Held keys buffer an initial rotation or hold, recorded once per spawn.
+        if tetris.current_piece.is_none() {
+            let keyboard = event_pump.keyboard_state();
+            if keyboard.is_scancode_pressed(Scancode::Up) && !tetris.initial_rotation {
+                apply_input(&mut tetris, &mut replay, Input::Rotate, millis);
+            }
+            let hold = keyboard.is_scancode_pressed(Scancode::C) || keyboard.is_scancode_pressed(Scancode::LShift);
+            if hold && !tetris.initial_hold {
+                apply_input(&mut tetris, &mut replay, Input::Hold, millis);
+            }
+        }
 
-            if let Some(ref mut piece) = tetris.current_piece {
-                let x = piece.x;
-                let y = piece.y + 1;
+        lag += timer.elapsed().unwrap_or_default();
+        timer = SystemTime::now();
+        while lag >= frame_duration {
+            tetris.update();
+            if let Some(ref mut ghost) = ghost {
+                ghost.step(&tetris);
+            }
+            if tetris.mode == GameMode::Zen {
+                history.record(&tetris);
+            }
+            if puzzles.is_empty() && tetris.frame.is_multiple_of(AUTOSAVE_SECONDS * FPS) {
+                autosave(&tetris);
+            }
+            lag -= frame_duration;
+        }
+
+        scene.draw(&mut canvas, &texture_creator, &font, &tetris);
+        let start_x_point = scene.grid_x + TETRIS_HEIGHT as i32 * 10 + 20;
+        if let Some(puzzle) = puzzles.get(puzzle_nb) {
+            let status = if tetris.game_over { "Failed, R to retry" } else { "R to retry" };
+            display_text(&mut canvas, &texture_creator, &font, &puzzle.name, start_x_point, 195);
+            display_text(&mut canvas, &texture_creator, &font, status, start_x_point, 230);
+        }
+        if tetris.mode == GameMode::Zen {
+            display_text(&mut canvas, &texture_creator, &font, "Z to undo", start_x_point, 195);
+            display_text(&mut canvas, &texture_creator, &font, "Backspace to rewind", start_x_point, 230);
+        }
+        if let Some(ref ghost) = ghost {
+            ghost.draw(&mut canvas, &texture_creator, &font, &tetris, start_x_point);
+        }
+
+        canvas.present();
 
-                make_permanent = !piece.change_position(&tetris.game_map, x, y);
+        if quit {
+            print_game_information(&tetris);
+            if puzzles.is_empty() && !tetris.game_over && !tetris.is_finished() {
+                autosave(&tetris);
+                println!("Game saved, run `tetris resume` to continue it");
             }
-            if make_permanent {
-                tetris.make_permanent();
+            break;
+        }
+
+        // A game that has ended can no longer be resumed.
+        if puzzles.is_empty() && (tetris.game_over || tetris.is_finished()) {
+            let _ = fs::remove_file(save::autosave_path());
+        }
+
+        // A failed puzzle stays on screen until it is retried.
+        if tetris.game_over && puzzles.is_empty() {
+            print_game_information(&tetris);
+            if let GameMode::Fading { .. } | GameMode::Invisible = tetris.mode {
+                // The last frame shows the whole board, leave it up until a key is pressed.
+                wait_for_exit(&mut event_pump);
             }
-            timer = SystemTime::now();
+            break;
+        }
+
+        if tetris.is_finished() && puzzle_nb + 1 < puzzles.len() {
+            println!("Solved: {}", puzzles[puzzle_nb].name);
+            puzzle_nb += 1;
+            tetris = puzzles[puzzle_nb].start();
+        } else if tetris.is_finished() {
+            print_game_information(&tetris);
+            display_victory(&tetris, &mut canvas, &texture_creator, &font, &mut event_pump, width);
+            break;
         }
 
+        sleep(Duration::from_millis(1000 / 60));
+    }
+
+    if let Some(mut replay) = replay {
+        replay.finish(&tetris);
+        let path = replay.default_path();
+        match replay.write(&path) {
+            Ok(()) => println!("Replay saved to {}", path.display()),
+            Err(err) => eprintln!("Failed to save the replay: {}", err),
+        }
+    }
+}
+
+/// `tetris verify <replay> [score lines [time]]`, the claim defaulting to the
+/// result recorded in the replay.
+fn verify_command(args: &[String]) -> Result<bool, String> {
+    let path = args.first().ok_or("Usage: tetris verify <replay> [score lines [time]]")?;
+    let replay = Replay::read(Path::new(path))?;
+    let number = |index: usize, default: u32| match args.get(index) {
+        Some(arg) => arg.parse().map_err(|_| format!("Invalid number: {}", arg)),
+        None => Ok(default),
+    };
+    let claim = verify::Claim {
+        score: number(1, replay.score)?,
+        lines: number(2, replay.lines)?,
+        time: match args.get(3) {
+            Some(arg) => Some(mode::parse_time(arg).ok_or_else(|| format!("Invalid time: {}", arg))?),
+            None => None,
+        },
+    };
+    verify::verify(&replay, claim)
+}
+
+/// Applies a player input that came `millis` milliseconds after the last
+/// tick, recording it in the replay of the game.
+fn apply_input(tetris: &mut Tetris, replay: &mut Option<Replay>, input: Input, millis: u32) {
+    tetris.apply_at(input, millis);
+    if let Some(replay) = replay {
+        replay.record(tetris.frame, tetris.input_millis, input);
+    }
+}
+
+/// Textures and layout shared by every screen showing a game.
+struct Scene<'a> {
+    grid: Texture<'a>,
+    border: Texture<'a>,
+    preview: Texture<'a>,
+    hold: Texture<'a>,
+    textures: [Texture<'a>; 9],
+    height: u32,
+    grid_x: i32,
+    grid_y: i32,
+}
+
+impl Scene<'_> {
+    /// Draws the board, HUD, previews and pieces of a game, without
+    /// presenting the canvas so that callers can add their own text.
+    fn draw(&self, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, tetris: &Tetris) {
         canvas.set_draw_color(Color::RGB(255, 0, 0));
         canvas.clear();
 
         canvas.copy(
-            &border,
+            &self.border,
             None,
             Rect::new(
                 10,
-                (height - TETRIS_HEIGHT as u32 * 16) as i32 / 2 - 10,
+                (self.height - TETRIS_HEIGHT as u32 * 16) as i32 / 2 - 10,
                 TETRIS_HEIGHT as u32 * 10 + 20,
                 TETRIS_HEIGHT as u32 * 16 + 20,
             )
         ).expect("Failed to copy border");
         canvas.copy(
-            &grid,
+            &self.grid,
             None,
             Rect::new(
                 20,
-                (height - TETRIS_HEIGHT as u32 * 16) as i32 / 2,
+                (self.height - TETRIS_HEIGHT as u32 * 16) as i32 / 2,
                 TETRIS_HEIGHT as u32 * 10,
                 TETRIS_HEIGHT as u32 * 16,
             )
         ).expect("Failed to copy grid");
         canvas.copy(
-            &preview,
+            &self.preview,
             None,
             Rect::new(
-                grid_x + TETRIS_HEIGHT as i32 * 10 + 20,
-                height as i32 / 2,
+                self.grid_x + TETRIS_HEIGHT as i32 * 10 + 20,
+                self.height as i32 / 2,
                 TETRIS_HEIGHT as u32 * 4,
                 TETRIS_HEIGHT as u32 * 4,
             )
         ).expect("Failed to copy preview");
-        
-        display_game_information(&tetris, &mut canvas, &texture_creator, &font, grid_x + TETRIS_HEIGHT as i32 * 10 + 20);
+        canvas.copy(
+            &self.hold,
+            None,
+            Rect::new(
+                self.grid_x + TETRIS_HEIGHT as i32 * 10 + 20,
+                self.height as i32 / 2 + TETRIS_HEIGHT as i32 * 4 + 20,
+                TETRIS_HEIGHT as u32 * 4,
+                TETRIS_HEIGHT as u32 * 2,
+            )
+        ).expect("Failed to copy hold");
 
-        if tetris.current_piece.is_none() {
-            let current_piece = next_piece;
-            next_piece = tetris.create_new_tetrimino();
-            if !current_piece.test_current_position(&tetris.game_map) {
-                print_game_information(&tetris);
-                break;
-            }
-            tetris.current_piece = Some(current_piece);
+        display_game_information(tetris, canvas, texture_creator, font, self.grid_x + TETRIS_HEIGHT as i32 * 10 + 20);
+        if let Some(ref piece) = tetris.next_piece {
+            piece.preview(canvas, &self.textures, self.grid_x, self.height);
         }
-
-        next_piece.preview(&mut canvas, &textures, grid_x, height);
-
-        tetris.draw(&mut canvas, &textures, grid_x, grid_y);
-
-        let mut quit = false;
-        if !handle_events(&mut tetris, &mut quit, &mut timer, &mut event_pump) {
-            if let Some(ref mut piece) = tetris.current_piece {
-                piece.draw(&mut canvas, &textures, grid_x, grid_y);
-            }
+        if let Some(ref piece) = tetris.hold_piece {
+            piece.preview_at(
+                canvas,
+                &self.textures,
+                self.grid_x + TETRIS_HEIGHT as i32 * 10 + 20,
+                self.height as i32 / 2 + TETRIS_HEIGHT as i32 * 4 + 20,
+            );
         }
 
-        canvas.present();
+        tetris.draw(canvas, &self.textures, self.grid_x, self.grid_y);
 
-        if quit {
-            print_game_information(&tetris);
-            break;
+        if let Some(ref piece) = tetris.current_piece {
+            piece.draw(canvas, &self.textures, self.grid_x, self.grid_y);
         }
-
-        sleep(Duration::from_millis(1000 / 60));
     }
 }
 
@@ -206,73 +529,116 @@ fn create_texture_rect<'a>(
     }
 }
 
+fn autosave(tetris: &Tetris) {
+    if let Err(err) = save::write(tetris, &save::autosave_path()) {
+        eprintln!("Autosave failed: {}", err);
+    }
+}
+
 fn print_game_information(tetris: &Tetris) {
-    println!("Game over...");
+    if tetris.is_finished() {
+        println!("{} complete!", tetris.mode.name());
+    } else {
+        println!("Game over...");
+    }
     println!("Score: {}", tetris.score);
     println!("Lines: {}", tetris.nb_lines);
     println!("Current level: {}", tetris.current_level);
+    if let GameMode::Sprint { .. } | GameMode::Dig { .. } | GameMode::Master = tetris.mode {
+        println!("Time: {}", format_time(tetris.elapsed_millis()));
+    }
+    if tetris.mode == GameMode::Master {
+        println!("Grade: {}", tetris.grade());
+        let mut previous = 0;
+        for (i, section) in tetris.sections.iter().enumerate() {
+            println!("Section {}: {}", i + 1, format_time(section.time - previous));
+            previous = section.time;
+        }
+    }
 }
 
-fn handle_events(tetris: &mut Tetris, quit: &mut bool, timer: &mut SystemTime, event_pump: &mut sdl2::EventPump) -> bool {
-    let mut make_permanent = false;
+fn display_victory(tetris: &Tetris, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, event_pump: &mut sdl2::EventPump, width: u32) {
+    let mut texts = vec![
+        format!("{} complete!", tetris.mode.name()),
+        format!("Score: {}", tetris.score),
+        format!("Lines: {}", tetris.nb_lines),
+        format!("Level: {}", tetris.current_level),
+        format!("Time: {}", format_time(tetris.elapsed_millis())),
+    ];
+    if tetris.mode == GameMode::Master {
+        texts.push(format!("Grade: {}", tetris.grade()));
+    }
+    texts.push("Press Escape to quit".to_owned());
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    for (i, text) in texts.iter().enumerate() {
+        let texture = create_texture_from_text(texture_creator, font, text, Color::RGB(255, 255, 255)).expect("Failed text");
+        let x = (width as i32 - text.len() as i32 * 10) / 2;
+        canvas.copy(&texture, None, get_rect_from_text(text, x, 200 + i as i32 * 50)).expect("Cannot render text");
+    }
+    canvas.present();
 
-    if let Some(ref mut piece) = tetris.current_piece {
-        let mut tmp_x = piece.x;
-        let mut tmp_y = piece.y;
+    wait_for_exit(event_pump);
+}
 
+fn wait_for_exit(event_pump: &mut sdl2::EventPump) {
+    loop {
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    *quit = true;
-                    break;
-                }
-                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
-                    *timer = SystemTime::now();
-                    tmp_y += 1;
-                }
-                Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
-                    tmp_x += 1;
-                }
-                Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
-                    tmp_x -= 1;
-                }
-                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
-                    piece.rotate(&tetris.game_map);
-                }
-                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
-                    let x = piece.x;
-                    let mut y = piece.y;
-
-                    while piece.change_position(&tetris.game_map, x, y + 1) {
-                        y += 1;
-                    }
-                    make_permanent = true;
-                }
+                Event::Quit { .. }
+                | Event::KeyDown { keycode: Some(Keycode::Escape), .. }
+                | Event::KeyDown { keycode: Some(Keycode::Return), .. } => return,
                 _ => {}
             }
         }
+        sleep(Duration::from_millis(1000 / 60));
+    }
+}
 
-        if !make_permanent {
-            if !piece.change_position(&tetris.game_map, tmp_x, tmp_y) && tmp_y != piece.y {
-                make_permanent = true;
+fn handle_events(
+    inputs: &mut Vec<Input>,
+    quit: &mut bool,
+    retry: &mut bool,
+    undo: &mut bool,
+    rewind: &mut bool,
+    event_pump: &mut sdl2::EventPump,
+) {
+    for event in event_pump.poll_iter() {
+        match event {
+            Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                *quit = true;
+                break;
             }
-        }
-    } else {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    *quit = true;
-                    break;
+            Event::KeyDown { keycode: Some(Keycode::R), .. } => {
+                *retry = true;
+            }
+            Event::KeyDown { keycode: Some(Keycode::Z), .. } => {
+                *undo = true;
+            }
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                *rewind = true;
+            }
+            Event::KeyDown { keycode: Some(keycode), .. } => {
+                if let Some(input) = key_to_input(keycode) {
+                    inputs.push(input);
                 }
-                _ => {}
             }
+            _ => {}
         }
     }
-    if make_permanent {
-        tetris.make_permanent();
-        *timer = SystemTime::now();
+}
+
+fn key_to_input(keycode: Keycode) -> Option<Input> {
+    match keycode {
+        Keycode::Left => Some(Input::Left),
+        Keycode::Right => Some(Input::Right),
+        Keycode::Down => Some(Input::SoftDrop),
+        Keycode::Space => Some(Input::HardDrop),
+        Keycode::Up => Some(Input::Rotate),
+        Keycode::C | Keycode::LShift => Some(Input::Hold),
+        _ => None,
     }
-    make_permanent
 }
 
 fn create_texture_from_text<'a>(texture_creator: &'a TextureCreator<WindowContext>, font: &sdl2::ttf::Font, text: &str, color: Color) -> Option<Texture<'a>> {
@@ -283,14 +649,36 @@ fn create_texture_from_text<'a>(texture_creator: &'a TextureCreator<WindowContex
     }
 }
 
+fn display_text(canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, text: &str, x: i32, y: i32) {
+    let texture = create_texture_from_text(texture_creator, font, text, Color::RGB(255, 255, 255)).expect("Failed text");
+    canvas.copy(&texture, None, get_rect_from_text(text, x, y)).expect("Cannot render text");
+}
+
 fn get_rect_from_text(text: &str, x: i32, y: i32) -> Option<Rect> {
     Some(Rect::new(x, y, text.len() as u32 * 10, 30))
 }
 
 fn display_game_information<'a>(tetris: &Tetris, canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, font: &sdl2::ttf::Font, start_x_point: i32) {
     let score_text = format!("Score: {}", tetris.score);
-    let lines_sent_text = format!("Lines: {}", tetris.nb_lines);
-    let level_text = format!("Level: {}", tetris.current_level);
+    let lines_sent_text = match tetris.mode {
+        GameMode::Sprint { lines } => format!("Lines: {}/{}", tetris.nb_lines, lines),
+        GameMode::Dig { lines, .. } => format!("Garbage: {}/{}", tetris.garbage_cleared, lines),
+        GameMode::BType { .. } => format!("Lines left: {}", BTYPE_LINES.saturating_sub(tetris.nb_lines)),
+        GameMode::Puzzle { goal } => goal.description(),
+        GameMode::Mission => format!("Mission: {}/{}", (tetris.mission_nb + 1).min(MISSIONS.len()), MISSIONS.len()),
+        _ => format!("Lines: {}", tetris.nb_lines),
+    };
+    let level_text = match tetris.mode {
+        GameMode::Ultra { duration } => format!("Time: {}", format_time(duration.saturating_sub(tetris.elapsed_millis()))),
+        GameMode::Puzzle { .. } => format!("Pieces left: {}", tetris.pieces_left().unwrap_or(0)),
+        GameMode::Mission => format!("Time: {}", format_time(tetris.mission_time_left())),
+        GameMode::Master => {
+            // The next hundred, except that the game ends at 999.
+            let target = ((tetris.current_level / 100 + 1) * 100).min(999);
+            format!("Level: {}/{}", tetris.current_level, target)
+        }
+        _ => format!("Level: {}", tetris.current_level),
+    };
 
     let score = create_texture_from_text(&texture_creator, &font, &score_text, Color::RGB(255, 255, 255)).expect("Failed text");
     let lines = create_texture_from_text(&texture_creator, &font, &lines_sent_text, Color::RGB(255, 255, 255)).expect("Failed text");
@@ -299,4 +687,39 @@ fn display_game_information<'a>(tetris: &Tetris, canvas: &mut Canvas<Window>, te
     canvas.copy(&score, None, get_rect_from_text(&score_text, start_x_point, 90)).expect("Cannot render text");
     canvas.copy(&lines, None, get_rect_from_text(&lines_sent_text, start_x_point, 125)).expect("Cannot render text");
     canvas.copy(&level, None, get_rect_from_text(&level_text, start_x_point, 160)).expect("Cannot render text");
+
+    if let GameMode::Sprint { .. } | GameMode::Dig { .. } | GameMode::Master = tetris.mode {
+        let time_text = format!("Time: {}", format_time(tetris.elapsed_millis()));
+        let time = create_texture_from_text(texture_creator, font, &time_text, Color::RGB(255, 255, 255)).expect("Failed text");
+        canvas.copy(&time, None, get_rect_from_text(&time_text, start_x_point, 195)).expect("Cannot render text");
+    }
+
+    if tetris.mode == GameMode::Master {
+        let grade_text = format!("Grade: {}", tetris.grade());
+        let grade = create_texture_from_text(texture_creator, font, &grade_text, Color::RGB(255, 255, 255)).expect("Failed text");
+        canvas.copy(&grade, None, get_rect_from_text(&grade_text, start_x_point, 230)).expect("Cannot render text");
+    }
+
+    if let Some(mission) = tetris.mission() {
+        let progress_text = format!("Done: {}/{}", tetris.mission_progress, mission.count);
+        display_text(canvas, texture_creator, font, &progress_text, start_x_point, 195);
+        for (i, line) in wrap_text(mission.description, 16).iter().enumerate() {
+            display_text(canvas, texture_creator, font, line, start_x_point, 230 + i as i32 * 35);
+        }
+    }
+}
+
+/// Splits `text` into lines of at most `width` characters, between words.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_owned()),
+        }
+    }
+    lines
 }
@@ -0,0 +1,57 @@
+use crate::config::GRAVITY_UNIT;
+
+pub const MAX_LEVEL: u32 = 999;
+
+/// Internal gravity in 1/256 rows per tick, from the level it applies at.
+/// 5120 is 20G: the piece lands on the frame it appears.
+const GRAVITY: [(u32, u32); 30] = [
+    (0, 4), (30, 6), (35, 8), (40, 10), (50, 12), (60, 16), (70, 32), (80, 48), (90, 64), (100, 80),
+    (120, 96), (140, 112), (160, 128), (170, 144), (200, 4), (220, 32), (230, 64), (233, 96), (236, 128), (239, 160),
+    (243, 192), (247, 224), (251, 256), (300, 512), (330, 768), (360, 1024), (400, 1280), (420, 1024), (450, 768),
+    (500, 5120),
+];
+
+/// Entry delay and lock delay in ticks, from the level they apply at.
+const DELAYS: [(u32, u32, u32); 6] = [
+    (0, 30, 30), (500, 25, 30), (600, 16, 30), (700, 12, 25), (800, 8, 20), (900, 6, 17),
+];
+
+/// Minimum score for each grade, worst to best.
+const GRADES: [(u32, &str); 18] = [
+    (0, "9"), (400, "8"), (800, "7"), (1400, "6"), (2000, "5"), (3500, "4"), (5500, "3"), (8000, "2"), (12000, "1"),
+    (16000, "S1"), (22000, "S2"), (30000, "S3"), (40000, "S4"), (52000, "S5"), (66000, "S6"), (82000, "S7"),
+    (100000, "S8"), (120000, "S9"),
+];
+
+/// Grand Master requirements: score and time at level 300 and 500, then the
+/// score and total time when reaching level 999.
+const GM_CHECKPOINTS: [(usize, u32, u32); 3] = [(2, 12000, 255_000), (4, 40000, 450_000), (9, 126000, 810_000)];
+
+/// Time and score when a section of 100 levels was completed.
+#[derive(Clone, Copy, Debug)]
+pub struct Section {
+    pub time: u32,
+    pub score: u32,
+}
+
+pub fn gravity(level: u32) -> u32 {
+    let internal = GRAVITY.iter().rev().find(|(from, _)| level >= *from).map_or(4, |(_, gravity)| *gravity);
+    internal * (GRAVITY_UNIT / 256)
+}
+
+pub fn delays(level: u32) -> (u32, u32) {
+    let (_, entry, lock) = DELAYS.iter().rev().find(|(from, _, _)| level >= *from).unwrap_or(&DELAYS[0]);
+    (*entry, *lock)
+}
+
+pub fn grade(score: u32, sections: &[Section]) -> &'static str {
+    let is_grand_master = GM_CHECKPOINTS.iter().all(|(section, min_score, max_time)| {
+        sections
+            .get(*section)
+            .is_some_and(|section| section.score >= *min_score && section.time <= *max_time)
+    });
+    if is_grand_master {
+        return "GM";
+    }
+    GRADES.iter().rev().find(|(min_score, _)| score >= *min_score).map_or("9", |(_, grade)| *grade)
+}
@@ -0,0 +1,29 @@
+use crate::game::Lock;
+
+/// An objective to complete `count` times within `time_limit` seconds, each
+/// lock counting when it clears exactly `lines` lines with the right piece.
+pub struct Mission {
+    pub description: &'static str,
+    pub lines: u32,
+    pub piece: Option<u8>,
+    pub t_spin: bool,
+    pub count: u32,
+    pub time_limit: u32,
+}
+
+pub const MISSIONS: [Mission; 6] = [
+    Mission { description: "Clear 3 singles", lines: 1, piece: None, t_spin: false, count: 3, time_limit: 45 },
+    Mission { description: "Clear 2 doubles using only L pieces", lines: 2, piece: Some(3), t_spin: false, count: 2, time_limit: 90 },
+    Mission { description: "Clear 2 triples", lines: 3, piece: None, t_spin: false, count: 2, time_limit: 120 },
+    Mission { description: "Do 3 T-spin singles in 60 seconds", lines: 1, piece: Some(7), t_spin: true, count: 3, time_limit: 60 },
+    Mission { description: "Clear a tetris", lines: 4, piece: Some(1), t_spin: false, count: 1, time_limit: 90 },
+    Mission { description: "Do a T-spin double", lines: 2, piece: Some(7), t_spin: true, count: 1, time_limit: 120 },
+];
+
+impl Mission {
+    pub fn is_completed_by(&self, lock: &Lock) -> bool {
+        lock.lines == self.lines
+            && self.piece.is_none_or(|piece| piece == lock.piece)
+            && (!self.t_spin || lock.t_spin)
+    }
+}
@@ -0,0 +1,171 @@
+use crate::config::{
+    BTYPE_HEIGHTS, DIG_LINES, DIG_MESSINESS, FADE_TIME, FADING_SECONDS, LEVEL_TIMES, MARATHON_LINES,
+    MARATHON_MAX_LEVEL, SPRINT_LINES, ULTRA_SECONDS,
+};
+use crate::puzzle::Goal;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    Endless,
+    Sprint { lines: u32 },
+    /// Score attack against the clock, `duration` in milliseconds.
+    Ultra { duration: u32 },
+    /// Guideline marathon: a level every 10 lines, `lines: None` for endless.
+    Marathon { start_level: u32, lines: Option<u32> },
+    /// Cheese race: clear `lines` garbage rows. `messiness` is the percentage
+    /// chance that the hole moves between two consecutive garbage rows.
+    Dig { lines: u32, messiness: u32 },
+    /// Classic B-type: clear 25 lines starting from a board pre-filled up to
+    /// `height` (0-5) with scattered blocks. The level never changes.
+    BType { start_level: u32, height: u32 },
+    /// Modelled on The Grand Master: levels 0-999 with section stops, gravity
+    /// up to 20G and a grade at the end.
+    Master,
+    /// Locked blocks fade out `delay` milliseconds after locking.
+    Fading { delay: u32 },
+    /// Locked blocks disappear as soon as they lock.
+    Invisible,
+    /// Every block covers 2x2 cells, as if playing on a 5 cell wide board.
+    Big,
+    /// A fixed board and piece sequence loaded from a puzzle file.
+    Puzzle { goal: Goal },
+    /// A series of scripted objectives, each against its own time limit.
+    Mission,
+    /// Practice without gravity, timer or top-out.
+    Zen,
+}
+
+impl GameMode {
+    /// Reads the mode from the command line, e.g. `tetris sprint 20`,
+    /// `tetris ultra 180` (seconds), `tetris marathon 5 endless`,
+    /// `tetris dig 100 50`, `tetris btype 5 3` (level, height), `tetris master`
+    /// `tetris fading 3` (seconds), `tetris big`, `tetris mission` or `tetris zen`.
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<GameMode, String> {
+        let name = match args.next() {
+            Some(name) => name,
+            None => return Ok(GameMode::Endless),
+        };
+        let mut number = |default: u32| -> Result<u32, String> {
+            match args.next() {
+                Some(arg) => arg.parse().map_err(|_| format!("Invalid number: {}", arg)),
+                None => Ok(default),
+            }
+        };
+
+        match name.as_str() {
+            "endless" => Ok(GameMode::Endless),
+            "sprint" => Ok(GameMode::Sprint { lines: number(SPRINT_LINES)? }),
+            "ultra" => {
+                let duration = number(ULTRA_SECONDS)?.checked_mul(1000).ok_or("Invalid duration")?;
+                Ok(GameMode::Ultra { duration })
+            }
+            "marathon" => {
+                let start_level = number(1)?;
+                if !(1..=MARATHON_MAX_LEVEL).contains(&start_level) {
+                    return Err(format!("Starting level must be between 1 and {}", MARATHON_MAX_LEVEL));
+                }
+                let lines = match args.next().as_deref() {
+                    None => Some(MARATHON_LINES),
+                    Some("endless") => None,
+                    Some(arg) => Some(arg.parse().map_err(|_| format!("Invalid number: {}", arg))?),
+                };
+                Ok(GameMode::Marathon { start_level, lines })
+            }
+            "dig" => {
+                let lines = number(DIG_LINES)?;
+                let messiness = number(DIG_MESSINESS)?.min(100);
+                Ok(GameMode::Dig { lines, messiness })
+            }
+            "btype" => {
+                let start_level = number(1)?;
+                if !(1..=LEVEL_TIMES.len() as u32).contains(&start_level) {
+                    return Err(format!("Level must be between 1 and {}", LEVEL_TIMES.len()));
+                }
+                let height = number(0)?;
+                if height as usize >= BTYPE_HEIGHTS.len() {
+                    return Err(format!("Height must be between 0 and {}", BTYPE_HEIGHTS.len() - 1));
+                }
+                Ok(GameMode::BType { start_level, height })
+            }
+            "master" => Ok(GameMode::Master),
+            "fading" => {
+                // Leave room for the fade itself, which follows the delay.
+                let delay = number(FADING_SECONDS)?
+                    .checked_mul(1000)
+                    .filter(|delay| delay.checked_add(FADE_TIME).is_some())
+                    .ok_or("Invalid delay")?;
+                Ok(GameMode::Fading { delay })
+            }
+            "invisible" => Ok(GameMode::Invisible),
+            "big" => Ok(GameMode::Big),
+            "mission" => Ok(GameMode::Mission),
+            "zen" => Ok(GameMode::Zen),
+            _ => Err(format!("Unknown game mode: {}", name)),
+        }
+    }
+
+    /// The command line arguments `from_args` reads this mode back from.
+    /// Puzzles come from files rather than arguments and only give their name.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![self.name().to_lowercase().replace('-', "")];
+        let numbers = match *self {
+            GameMode::Sprint { lines } => vec![lines],
+            GameMode::Ultra { duration } => vec![duration / 1000],
+            GameMode::Marathon { start_level, lines } => {
+                args.push(start_level.to_string());
+                args.push(lines.map_or("endless".to_owned(), |lines| lines.to_string()));
+                vec![]
+            }
+            GameMode::Dig { lines, messiness } => vec![lines, messiness],
+            GameMode::BType { start_level, height } => vec![start_level, height],
+            GameMode::Fading { delay } => vec![delay / 1000],
+            _ => vec![],
+        };
+        args.extend(numbers.iter().map(u32::to_string));
+        args
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "Endless",
+            GameMode::Sprint { .. } => "Sprint",
+            GameMode::Ultra { .. } => "Ultra",
+            GameMode::Marathon { .. } => "Marathon",
+            GameMode::Dig { .. } => "Dig",
+            GameMode::BType { .. } => "B-type",
+            GameMode::Master => "Master",
+            GameMode::Fading { .. } => "Fading",
+            GameMode::Invisible => "Invisible",
+            GameMode::Big => "Big",
+            GameMode::Puzzle { .. } => "Puzzle",
+            GameMode::Mission => "Mission",
+            GameMode::Zen => "Zen",
+        }
+    }
+
+    pub fn start_level(&self) -> u32 {
+        match self {
+            GameMode::Marathon { start_level, .. } | GameMode::BType { start_level, .. } => *start_level,
+            GameMode::Master => 0,
+            _ => 1,
+        }
+    }
+}
+
+pub fn format_time(millis: u32) -> String {
+    format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
+
+/// Reads a time written by `format_time`, or a plain number of milliseconds.
+pub fn parse_time(text: &str) -> Option<u32> {
+    let (minutes, seconds) = match text.split_once(':') {
+        Some((minutes, seconds)) => (minutes.parse::<u32>().ok()?, seconds),
+        None => return text.parse().ok(),
+    };
+    let (seconds, millis) = seconds.split_once('.').unwrap_or((seconds, "0"));
+    if millis.len() > 3 {
+        return None;
+    }
+    let millis = millis.parse::<u32>().ok()? * 10u32.pow(3 - millis.len() as u32);
+    Some(minutes * 60_000 + seconds.parse::<u32>().ok()? * 1000 + millis)
+}
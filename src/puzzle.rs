@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::Path;
+
+use crate::{board, fumen, game::Tetris, mode::GameMode, tetriminos::*};
+
+/// What has to be done to solve a puzzle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    Lines(u32),
+    PerfectClear,
+    TSpinDouble,
+}
+
+impl Goal {
+    fn parse(text: &str) -> Result<Goal, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words.as_slice() {
+            ["lines", lines] => lines
+                .parse()
+                .map(Goal::Lines)
+                .map_err(|_| format!("Invalid number of lines: {}", lines)),
+            ["perfect", "clear"] => Ok(Goal::PerfectClear),
+            ["tspin", "double"] => Ok(Goal::TSpinDouble),
+            _ => Err(format!("Unknown goal: {}", text)),
+        }
+    }
+
+    /// The goal as written in puzzle files.
+    fn to_text(self) -> String {
+        match self {
+            Goal::Lines(lines) => format!("lines {}", lines),
+            Goal::PerfectClear => "perfect clear".to_owned(),
+            Goal::TSpinDouble => "tspin double".to_owned(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Goal::Lines(1) => "Clear 1 line".to_owned(),
+            Goal::Lines(lines) => format!("Clear {} lines", lines),
+            Goal::PerfectClear => "Perfect clear".to_owned(),
+            Goal::TSpinDouble => "T-spin double".to_owned(),
+        }
+    }
+}
+
+/// A starting board with the exact pieces to solve it with. Puzzle files look
+/// like this, the board being bottom aligned and using the piece letters
+/// with `G` for garbage and `.` for empty cells:
+///
+/// ```text
+/// name: Fill the well
+/// goal: lines 2
+/// pieces: IO
+/// hold: T
+/// board:
+/// GGGGGGGGG.
+/// GGGGGGGGG.
+/// ```
+///
+/// A `fumen: v115@...` line can give the board instead, from its first page.
+pub struct Puzzle {
+    pub name: String,
+    pub game_map: Vec<Vec<u8>>,
+    pub pieces: Vec<u8>,
+    pub hold: Option<u8>,
+    pub goal: Goal,
+}
+
+impl Puzzle {
+    pub fn parse(name: &str, text: &str) -> Result<Puzzle, String> {
+        let mut puzzle = Puzzle {
+            name: name.to_owned(),
+            game_map: vec![vec![0; 10]; 16],
+            pieces: Vec::new(),
+            hold: None,
+            goal: Goal::Lines(1),
+        };
+        let mut board = Vec::new();
+        let mut in_board = false;
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            if in_board {
+                board.push(parse_pieces(line)?);
+                continue;
+            }
+            let (key, value) = line.split_once(':').ok_or_else(|| format!("Invalid line: {}", line))?;
+            let value = value.trim();
+            match key.trim() {
+                // An empty name keeps the one from the file name.
+                "name" if value.is_empty() => {}
+                "name" => puzzle.name = value.to_owned(),
+                "goal" => puzzle.goal = Goal::parse(value)?,
+                "pieces" => puzzle.pieces = parse_pieces(value)?,
+                "hold" => puzzle.hold = parse_pieces(value)?.first().copied(),
+                "board" => in_board = true,
+                "fumen" => board = fumen::decode(value)?.swap_remove(0).game_map,
+                key => return Err(format!("Unknown key: {}", key)),
+            }
+        }
+
+        if puzzle.pieces.iter().chain(puzzle.hold.iter()).any(|id| *id == 0 || *id > 7) {
+            return Err("Pieces must be I, J, L, O, S, Z or T".to_owned());
+        }
+        if board.len() > puzzle.game_map.len() || board.iter().any(|line| line.len() != 10) {
+            return Err("The board must be at most 16 lines of 10 cells".to_owned());
+        }
+        let top = puzzle.game_map.len() - board.len();
+        for (line_nb, line) in board.into_iter().enumerate() {
+            puzzle.game_map[top + line_nb] = line;
+        }
+        Ok(puzzle)
+    }
+
+    pub fn load(path: &Path) -> Result<Puzzle, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let name = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        Puzzle::parse(&name, &text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Loads every `.txt` puzzle of a directory in file name order, or the
+    /// single puzzle at `path` when it is a file.
+    pub fn load_pack(path: &Path) -> Result<Vec<Puzzle>, String> {
+        if path.is_file() {
+            return Puzzle::load(path).map(|puzzle| vec![puzzle]);
+        }
+        let mut paths: Vec<_> = fs::read_dir(path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+            .collect();
+        if paths.is_empty() {
+            return Err(format!("{}: no puzzles found", path.display()));
+        }
+        paths.sort();
+        paths.iter().map(|path| Puzzle::load(path)).collect()
+    }
+
+    /// The puzzle in the file format `parse` reads, with the empty top of the
+    /// board left out.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("name: {}\ngoal: {}\n", self.name, self.goal.to_text());
+        text += &format!("pieces: {}\n", self.pieces.iter().map(|id| letter_from_id(*id)).collect::<String>());
+        if let Some(hold) = self.hold {
+            text += &format!("hold: {}\n", letter_from_id(hold));
+        }
+        let top = self.game_map.iter().take_while(|line| line.iter().all(|case| *case == 0)).count();
+        text + "board:\n" + &board::to_text(&self.game_map[top..], None)
+    }
+
+    pub fn start(&self) -> Tetris {
+        let mut tetris = Tetris::new(GameMode::Puzzle { goal: self.goal });
+        tetris.game_map = self.game_map.clone();
+        tetris.hold_piece = self.hold.map(create_tetrimino);
+        tetris.set_sequence(&self.pieces);
+        tetris
+    }
+
+    /// An endless game from the puzzle's board and hold. Its pieces make up
+    /// the whole sequence when there are any, otherwise pieces are random.
+    pub fn play(&self) -> Tetris {
+        let mut tetris = Tetris::new(GameMode::Endless);
+        tetris.game_map = self.game_map.clone();
+        tetris.hold_piece = self.hold.map(create_tetrimino);
+        if !self.pieces.is_empty() {
+            tetris.set_sequence(&self.pieces);
+        }
+        tetris
+    }
+}
+
+/// Reads piece letters, `.` standing for an empty cell.
+fn parse_pieces(text: &str) -> Result<Vec<u8>, String> {
+    text.chars()
+        .filter(|letter| !letter.is_whitespace())
+        .map(|letter| match letter {
+            '.' => Ok(0),
+            _ => id_from_letter(letter).ok_or_else(|| format!("Unknown piece: {}", letter)),
+        })
+        .collect()
+}
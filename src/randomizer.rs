@@ -0,0 +1,38 @@
+/// Seeded piece and garbage generator. It is part of the game state, so a
+/// copy of a game carries on with exactly the same pieces as the original.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Randomizer {
+    pub state: u64,
+    /// Last piece dealt, rolled against once to avoid repeats.
+    pub prev: u8,
+}
+
+impl Randomizer {
+    pub fn new(seed: u64) -> Randomizer {
+        Randomizer { state: seed, prev: 7 }
+    }
+
+    /// SplitMix64.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..max`.
+    pub fn below(&mut self, max: usize) -> usize {
+        (self.next_u64() % max as u64) as usize
+    }
+
+    /// Id of the next random piece, from 1 to 7.
+    pub fn next_piece(&mut self) -> u8 {
+        let mut rand_num = self.below(7) as u8;
+        if self.prev == rand_num {
+            rand_num = self.below(7) as u8;
+        }
+        self.prev = rand_num;
+        rand_num + 1
+    }
+}
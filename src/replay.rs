@@ -0,0 +1,345 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    config::FPS,
+    game::{Input, Tetris},
+    mode::GameMode,
+    save::data_dir,
+};
+
+/// Bumped whenever the replay format or the engine rules change in a way
+/// that would make older replays play out differently.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Everything needed to play a game again: the mode, the seed of its pieces
+/// and every input with the engine tick it was applied at, along with the
+/// milliseconds it came after that tick. The result is kept alongside to list
+/// and check replays without playing them.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub mode: GameMode,
+    pub seed: u64,
+    /// Seconds since the Unix epoch when the game started.
+    pub date: u64,
+    /// Ticks played when the game ended.
+    pub length: u32,
+    pub score: u32,
+    pub lines: u32,
+    pub inputs: Vec<(u32, u32, Input)>,
+}
+
+impl Replay {
+    pub fn new(mode: GameMode, seed: u64) -> Replay {
+        Replay {
+            mode,
+            seed,
+            date: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |date| date.as_secs()),
+            length: 0,
+            score: 0,
+            lines: 0,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, frame: u32, millis: u32, input: Input) {
+        self.inputs.push((frame, millis, input));
+    }
+
+    /// Forgets the inputs from `frame` on, after the game went back in time.
+    pub fn truncate(&mut self, frame: u32) {
+        self.inputs.retain(|(input_frame, _, _)| *input_frame < frame);
+    }
+
+    /// Stores how the game ended.
+    pub fn finish(&mut self, tetris: &Tetris) {
+        self.length = tetris.frame;
+        self.score = tetris.score;
+        self.lines = tetris.nb_lines;
+    }
+
+    pub fn start(&self) -> Tetris {
+        Tetris::with_seed(self.mode, self.seed)
+    }
+
+    /// Plays the whole replay and returns the game as it ended.
+    pub fn play(&self) -> Tetris {
+        let mut playback = Playback::new(self);
+        while playback.step() {}
+        playback.tetris
+    }
+
+    /// Where a replay started now is saved by default.
+    pub fn default_path(&self) -> PathBuf {
+        replays_dir().join(format!("{}-{}.txt", self.date, self.mode.args().join("-")))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        }
+        fs::write(path, self.to_text()).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn read(path: &Path) -> Result<Replay, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Replay::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Header lines followed by the inputs, each written as the ticks since
+    /// the previous input, a letter and the milliseconds after the tick unless
+    /// zero, e.g. `12l 0l 30h7`.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "tetris replay {}\nmode: {}\nseed: {}\nfps: {}\ndate: {}\nlength: {}\nscore: {}\nlines: {}\ninputs:\n",
+            REPLAY_VERSION,
+            self.mode.args().join(" "),
+            self.seed,
+            FPS,
+            self.date,
+            self.length,
+            self.score,
+            self.lines,
+        );
+        let mut previous = 0;
+        for (i, (frame, millis, input)) in self.inputs.iter().enumerate() {
+            text.push_str(&format!("{}{}", frame - previous, input_code(*input)));
+            if *millis > 0 {
+                text.push_str(&millis.to_string());
+            }
+            text.push(if i % 16 == 15 { '\n' } else { ' ' });
+            previous = *frame;
+        }
+        text.push('\n');
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines().map(str::trim);
+        match lines.next() {
+            Some(header) if header == format!("tetris replay {}", REPLAY_VERSION) => {}
+            Some(header) if header.starts_with("tetris replay ") => {
+                return Err(format!("Unsupported replay version: {}", &header[14..]))
+            }
+            _ => return Err("Not a replay file".to_owned()),
+        }
+
+        let mut replay = Replay::new(GameMode::Endless, 0);
+        for line in lines.by_ref().take_while(|line| *line != "inputs:") {
+            let (key, value) = line.split_once(':').ok_or_else(|| format!("Invalid line: {}", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "mode" => replay.mode = GameMode::from_args(value.split_whitespace().map(str::to_owned))?,
+                "seed" => replay.seed = number(value)?,
+                "fps" if number::<u32>(value)? != FPS => return Err(format!("Recorded at {} ticks per second", value)),
+                "fps" => {}
+                "date" => replay.date = number(value)?,
+                "length" => replay.length = number(value)?,
+                "score" => replay.score = number(value)?,
+                "lines" => replay.lines = number(value)?,
+                key => return Err(format!("Unknown key: {}", key)),
+            }
+        }
+
+        let mut frame: u32 = 0;
+        for word in lines.flat_map(str::split_whitespace) {
+            let (delay, code) = word.split_at(word.find(|letter: char| !letter.is_ascii_digit()).unwrap_or(word.len()));
+            let (code, millis) = code.split_at(code.chars().next().map_or(0, char::len_utf8));
+            let millis = if millis.is_empty() { 0 } else { number(millis)? };
+            frame = frame
+                .checked_add(number::<u32>(delay)?)
+                .filter(|frame| *frame <= replay.length)
+                .ok_or_else(|| format!("Input after the end of the replay: {}", word))?;
+            let input = input_from_code(code).ok_or_else(|| format!("Unknown input: {}", word))?;
+            replay.inputs.push((frame, millis, input));
+        }
+        Ok(replay)
+    }
+}
+
+/// A replay being played back one tick at a time.
+pub struct Playback<'a> {
+    replay: &'a Replay,
+    pub tetris: Tetris,
+    next_input: usize,
+}
+
+impl<'a> Playback<'a> {
+    pub fn new(replay: &'a Replay) -> Playback<'a> {
+        Playback {
+            replay,
+            tetris: replay.start(),
+            next_input: 0,
+        }
+    }
+
+    /// Applies the inputs of the current tick then advances the game, the
+    /// same order as live play. Returns `false` once the replay is over.
+    pub fn step(&mut self) -> bool {
+        if self.is_done() {
+            return false;
+        }
+        self.apply_inputs();
+        self.tetris.update();
+        if self.is_done() {
+            // Inputs made after the last tick, just before quitting.
+            self.apply_inputs();
+        }
+        true
+    }
+
+    /// Plays until the next piece comes into play.
+    pub fn step_piece(&mut self) {
+        let nb_pieces = self.tetris.nb_pieces + 1;
+        while self.tetris.nb_pieces < nb_pieces && self.step() {}
+    }
+
+    pub fn restart(&mut self) {
+        self.tetris = self.replay.start();
+        self.next_input = 0;
+    }
+
+    /// Goes to the given tick. Going back plays the replay again from the
+    /// start, which takes a few milliseconds even for long games.
+    pub fn seek_frame(&mut self, frame: u32) {
+        if frame < self.tetris.frame {
+            self.restart();
+        }
+        while self.tetris.frame < frame && self.step() {}
+    }
+
+    /// Goes to the moment the `nb_pieces`th piece came into play.
+    pub fn seek_piece(&mut self, nb_pieces: u32) {
+        if nb_pieces <= self.tetris.nb_pieces {
+            self.restart();
+        }
+        while self.tetris.nb_pieces < nb_pieces && self.step() {}
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.tetris.frame >= self.replay.length || self.tetris.game_over || self.tetris.is_finished()
+    }
+
+    fn apply_inputs(&mut self) {
+        while let Some((frame, millis, input)) = self.replay.inputs.get(self.next_input) {
+            if *frame > self.tetris.frame {
+                break;
+            }
+            self.tetris.apply_at(*input, *millis);
+            self.next_input += 1;
+        }
+    }
+}
+
+pub fn replays_dir() -> PathBuf {
+    data_dir().join("replays")
+}
+
+fn input_code(input: Input) -> char {
+    match input {
+        Input::Left => 'l',
+        Input::Right => 'r',
+        Input::SoftDrop => 's',
+        Input::HardDrop => 'h',
+        Input::Rotate => 'u',
+        Input::Hold => 'c',
+    }
+}
+
+fn input_from_code(code: &str) -> Option<Input> {
+    match code {
+        "l" => Some(Input::Left),
+        "r" => Some(Input::Right),
+        "s" => Some(Input::SoftDrop),
+        "h" => Some(Input::HardDrop),
+        "u" => Some(Input::Rotate),
+        "c" => Some(Input::Hold),
+        _ => None,
+    }
+}
+
+fn number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("Invalid number: {}", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finder;
+
+    /// Plays every piece at its lowest spot for up to five minutes, holding
+    /// every fifth one and buffering a rotation for every third spawn, the
+    /// inputs recorded the way live play records them.
+    fn scripted_game(mode: GameMode, seed: u64) -> (Tetris, Replay) {
+        let mut tetris = Tetris::with_seed(mode, seed);
+        let mut replay = Replay::new(mode, seed);
+        while !tetris.game_over && !tetris.is_finished() && tetris.frame < 5 * 60 * FPS {
+            let inputs = match tetris.current_piece {
+                Some(_) if tetris.nb_pieces.is_multiple_of(5) && tetris.can_hold => vec![Input::Hold],
+                Some(ref piece) => finder::find_moves(&tetris.game_map, piece)
+                    .into_iter()
+                    .max_by_key(|found| found.y)
+                    .map_or(vec![Input::HardDrop], |found| found.inputs),
+                None if tetris.nb_pieces.is_multiple_of(3) && !tetris.initial_rotation => vec![Input::Rotate],
+                None => vec![],
+            };
+            for input in inputs {
+                tetris.apply_at(input, tetris.frame % 17);
+                replay.record(tetris.frame, tetris.input_millis, input);
+            }
+            tetris.update();
+        }
+        replay.finish(&tetris);
+        (tetris, replay)
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mode = GameMode::Marathon {
+            start_level: 3,
+            lines: None,
+        };
+        let (_, replay) = scripted_game(mode, 42);
+        let text = replay.to_text();
+        let parsed = Replay::parse(&text).unwrap();
+        assert_eq!(parsed.mode, replay.mode);
+        assert_eq!(parsed.seed, replay.seed);
+        assert_eq!(parsed.date, replay.date);
+        assert_eq!(parsed.length, replay.length);
+        assert_eq!(parsed.score, replay.score);
+        assert_eq!(parsed.lines, replay.lines);
+        assert_eq!(parsed.inputs, replay.inputs);
+        assert_eq!(parsed.to_text(), text);
+    }
+
+    #[test]
+    fn inputs_without_milliseconds() {
+        let text = "tetris replay 1\nmode: endless\nseed: 1\nlength: 40\ninputs:\n3l 0r5 37h\n";
+        let replay = Replay::parse(text).unwrap();
+        assert_eq!(
+            replay.inputs,
+            [(3, 0, Input::Left), (3, 5, Input::Right), (40, 0, Input::HardDrop)]
+        );
+        assert!(Replay::parse("tetris replay 1\nlength: 40\ninputs:\n41h\n").is_err());
+        assert!(Replay::parse("tetris replay 1\nlength: 40\ninputs:\n3x\n").is_err());
+    }
+
+    #[test]
+    fn playback_reproduces_the_game() {
+        for (mode, seed) in [
+            (GameMode::Endless, 7),
+            (GameMode::Sprint { lines: 4 }, 3),
+            (GameMode::Ultra { duration: 20_000 }, 5),
+        ] {
+            let (tetris, replay) = scripted_game(mode, seed);
+            let played = replay.play();
+            assert!(tetris.nb_lines > 0, "{} clears no lines", mode.name());
+            assert_eq!(played.score, tetris.score);
+            assert_eq!(played.nb_lines, tetris.nb_lines);
+            assert_eq!(played.frame, tetris.frame);
+            assert_eq!(played.finish_time, tetris.finish_time);
+            assert_eq!(played.game_map, tetris.game_map);
+        }
+    }
+}
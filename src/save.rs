@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::{
+    board,
+    config::{GRAVITY_UNIT, LEVEL_TIMES, MARATHON_MAX_LEVEL},
+    game::{Lock, Tetris},
+    master::{self, Section},
+    mode::GameMode,
+    randomizer::Randomizer,
+    tetriminos::*,
+};
+
+/// Bumped whenever the save format changes, older saves are then refused.
+pub const SAVE_VERSION: u32 = 2;
+
+/// Where the game keeps its files: `$XDG_DATA_HOME/tetris`, falling back to
+/// `~/.local/share/tetris`, `%APPDATA%\tetris` or the current directory.
+pub fn data_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("tetris")
+}
+
+pub fn autosave_path() -> PathBuf {
+    data_dir().join("autosave.txt")
+}
+
+/// Writes the game to `path`, through a temporary file so that a crash
+/// halfway never leaves a truncated save behind.
+pub fn write(tetris: &Tetris, path: &Path) -> Result<(), String> {
+    let text = to_text(tetris)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, text).map_err(|err| format!("{}: {}", tmp.display(), err))?;
+    fs::rename(&tmp, path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn read(path: &Path) -> Result<Tetris, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    from_text(&text).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// The complete engine state as text. The board uses the puzzle letters, the
+/// other lines are `key: value` pairs named after the `Tetris` fields.
+pub fn to_text(tetris: &Tetris) -> Result<String, String> {
+    if let GameMode::Puzzle { .. } = tetris.mode {
+        return Err("Puzzles cannot be saved".to_owned());
+    }
+    let piece = |piece: &Option<Tetrimino>| piece.as_ref().map_or("none".to_owned(), |piece| letter(piece.id()));
+    let flag = |flag: bool| if flag { "yes" } else { "no" };
+
+    let mut lines = vec![
+        format!("tetris save {}", SAVE_VERSION),
+        format!("mode: {}", tetris.mode.args().join(" ")),
+        format!("rng: {} {}", tetris.rng.state, tetris.rng.prev),
+        format!("frame: {}", tetris.frame),
+        format!("current_level: {}", tetris.current_level),
+        format!("score: {}", tetris.score),
+        format!("nb_lines: {}", tetris.nb_lines),
+        format!("nb_pieces: {}", tetris.nb_pieces),
+        format!("finish_time: {}", tetris.finish_time.map_or("none".to_owned(), |time| time.to_string())),
+        format!("game_over: {}", flag(tetris.game_over)),
+        format!(
+            "current_piece: {}",
+            tetris.current_piece.as_ref().map_or("none".to_owned(), |piece| format!(
+                "{} {} {} {} {}",
+                letter(piece.id()),
+                piece.x,
+                piece.y,
+                piece.current_state,
+                piece.scale
+            ))
+        ),
+        format!("next_piece: {}", piece(&tetris.next_piece)),
+        format!(
+            "sequence: {}",
+            tetris.sequence.as_ref().map_or("none".to_owned(), |sequence| {
+                sequence.iter().map(|id| letter_from_id(*id)).collect()
+            })
+        ),
+        format!("hold_piece: {}", piece(&tetris.hold_piece)),
+        format!("can_hold: {}", flag(tetris.can_hold)),
+        format!("initial_rotation: {}", flag(tetris.initial_rotation)),
+        format!("initial_hold: {}", flag(tetris.initial_hold)),
+        format!(
+            "last_lock: {}",
+            tetris.last_lock.map_or("none".to_owned(), |lock| format!(
+                "{} {} {} {} {} {} {}",
+                letter(lock.piece),
+                lock.x,
+                lock.y,
+                lock.rotation,
+                lock.lines,
+                flag(lock.t_spin),
+                flag(lock.perfect_clear)
+            ))
+        ),
+        format!("last_move_rotation: {}", flag(tetris.last_move_rotation)),
+        format!("garbage: {} {} {}", tetris.garbage_added, tetris.garbage_cleared, tetris.garbage_hole),
+        format!(
+            "sections: {}",
+            tetris
+                .sections
+                .iter()
+                .map(|section| format!("{}/{}", section.time, section.score))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        format!("mission: {} {} {}", tetris.mission_nb, tetris.mission_progress, tetris.mission_start),
+        format!("combo: {}", tetris.combo),
+        format!("soft_rows: {}", tetris.soft_rows),
+        format!("fall: {}", tetris.fall),
+        format!("lock_frames: {}", tetris.lock_frames),
+        format!("entry_frames: {}", tetris.entry_frames),
+        "board:".to_owned(),
+    ];
+    lines.extend(board::to_text(&tetris.game_map, None).lines().map(str::to_owned));
+    lines.push("lock_times:".to_owned());
+    lines.extend(tetris.lock_times.iter().map(|line| {
+        line.iter().map(u32::to_string).collect::<Vec<_>>().join(" ")
+    }));
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
+
+pub fn from_text(text: &str) -> Result<Tetris, String> {
+    let mut lines = text.lines().map(str::trim);
+    match lines.next() {
+        Some(header) if header == format!("tetris save {}", SAVE_VERSION) => {}
+        Some(header) if header.starts_with("tetris save ") => {
+            return Err(format!("Unsupported save version: {}", &header[12..]))
+        }
+        _ => return Err("Not a save file".to_owned()),
+    }
+
+    let mut fields = Fields(HashMap::new());
+    for line in lines.by_ref().take_while(|line| *line != "board:") {
+        let (key, value) = line.split_once(':').ok_or_else(|| format!("Invalid line: {}", line))?;
+        fields.0.insert(key.trim(), value.trim());
+    }
+    let board: Vec<&str> = lines.by_ref().take_while(|line| *line != "lock_times:").collect();
+    let lock_times: Vec<&str> = lines.filter(|line| !line.is_empty()).collect();
+
+    let mode = GameMode::from_args(fields.get("mode")?.split_whitespace().map(str::to_owned))?;
+    let mut tetris = Tetris::new(mode);
+
+    let game_map = match board::parse(&board.join("\n"))? {
+        (game_map, None) => game_map,
+        (_, Some(_)) => return Err("The current piece belongs on its own line, not on the board".to_owned()),
+    };
+    // The engine only plays on boards of the standard size.
+    if game_map.len() != tetris.game_map.len() || game_map[0].len() != tetris.game_map[0].len() {
+        return Err(format!(
+            "The board must be {} lines of {} cells",
+            tetris.game_map.len(),
+            tetris.game_map[0].len()
+        ));
+    }
+    tetris.game_map = game_map;
+    tetris.lock_times = lock_times
+        .iter()
+        .map(|line| line.split_whitespace().map(number).collect::<Result<Vec<_>, _>>())
+        .collect::<Result<_, _>>()?;
+    let width = tetris.game_map[0].len();
+    if tetris.lock_times.len() != tetris.game_map.len() || tetris.lock_times.iter().any(|line| line.len() != width) {
+        return Err("Invalid board".to_owned());
+    }
+
+    let rng = fields.numbers::<u64>("rng", 2)?;
+    tetris.rng = Randomizer {
+        state: rng[0],
+        prev: rng[1] as u8,
+    };
+    tetris.frame = fields.number("frame")?;
+    tetris.current_level = fields.number("current_level")?;
+    let levels = match mode {
+        GameMode::Master => 0..=master::MAX_LEVEL,
+        GameMode::Marathon { .. } => 1..=MARATHON_MAX_LEVEL,
+        _ => 1..=LEVEL_TIMES.len() as u32,
+    };
+    if !levels.contains(&tetris.current_level) {
+        return Err(format!("Invalid level: {}", tetris.current_level));
+    }
+    // Blocks can't have locked after the current tick.
+    if tetris.lock_times.iter().flatten().any(|time| *time > tetris.frame) {
+        return Err("Invalid lock times".to_owned());
+    }
+    tetris.score = fields.number("score")?;
+    tetris.nb_lines = fields.number("nb_lines")?;
+    tetris.nb_pieces = fields.number("nb_pieces")?;
+    tetris.finish_time = fields.optional("finish_time")?.map(number).transpose()?;
+    tetris.game_over = fields.flag("game_over")?;
+
+    tetris.current_piece = match fields.optional("current_piece")? {
+        None => None,
+        Some(value) => {
+            let words: Vec<&str> = value.split_whitespace().collect();
+            if words.len() != 5 {
+                return Err(format!("Invalid current_piece: {}", value));
+            }
+            let mut piece = create_tetrimino(piece_id(words[0])?);
+            piece.x = number(words[1])?;
+            piece.y = number(words[2])?;
+            piece.current_state = number(words[3])?;
+            piece.scale = number(words[4])?;
+            if piece.current_state as usize >= piece.states.len() || !(1..=2).contains(&piece.scale) {
+                return Err(format!("Invalid current_piece: {}", value));
+            }
+            Some(piece)
+        }
+    };
+    tetris.next_piece = fields.optional("next_piece")?.map(piece_id).transpose()?.map(create_tetrimino);
+    tetris.sequence = match fields.optional("sequence")? {
+        None => None,
+        Some(value) => Some(value.chars().map(|letter| piece_id(&letter.to_string())).collect::<Result<_, _>>()?),
+    };
+    tetris.hold_piece = fields.optional("hold_piece")?.map(piece_id).transpose()?.map(create_tetrimino);
+    tetris.can_hold = fields.flag("can_hold")?;
+    tetris.initial_rotation = fields.flag("initial_rotation")?;
+    tetris.initial_hold = fields.flag("initial_hold")?;
+
+    tetris.last_lock = match fields.optional("last_lock")? {
+        None => None,
+        Some(value) => match value.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [piece, x, y, rotation, lines, t_spin, perfect_clear] => Some(Lock {
+                piece: piece_id(piece)?,
+                x: number(x)?,
+                y: number(y)?,
+                rotation: number(rotation)?,
+                lines: number(lines)?,
+                t_spin: flag(t_spin)?,
+                perfect_clear: flag(perfect_clear)?,
+            }),
+            _ => return Err(format!("Invalid last_lock: {}", value)),
+        },
+    };
+    tetris.last_move_rotation = fields.flag("last_move_rotation")?;
+
+    let garbage = fields.numbers::<u32>("garbage", 3)?;
+    tetris.garbage_added = garbage[0];
+    tetris.garbage_cleared = garbage[1];
+    tetris.garbage_hole = garbage[2] as usize % width;
+    tetris.sections = fields
+        .get("sections")?
+        .split_whitespace()
+        .map(|section| match section.split_once('/') {
+            Some((time, score)) => Ok(Section {
+                time: number(time)?,
+                score: number(score)?,
+            }),
+            None => Err(format!("Invalid section: {}", section)),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mission = fields.numbers::<u32>("mission", 3)?;
+    tetris.mission_nb = mission[0] as usize;
+    tetris.mission_progress = mission[1];
+    tetris.mission_start = mission[2];
+    if tetris.mission_start > tetris.frame {
+        return Err(format!("Invalid mission: {}", fields.get("mission")?));
+    }
+    tetris.combo = fields.number("combo")?;
+    tetris.soft_rows = fields.number("soft_rows")?;
+    tetris.fall = fields.number("fall")?;
+    if tetris.fall >= GRAVITY_UNIT {
+        return Err(format!("Invalid fall: {}", tetris.fall));
+    }
+    tetris.lock_frames = fields.number("lock_frames")?;
+    tetris.entry_frames = fields.number("entry_frames")?;
+
+    if tetris.current_piece.as_ref().is_some_and(|piece| !piece.test_current_position(&tetris.game_map)) {
+        return Err("The current piece overlaps the board".to_owned());
+    }
+    Ok(tetris)
+}
+
+struct Fields<'a>(HashMap<&'a str, &'a str>);
+
+impl<'a> Fields<'a> {
+    fn get(&self, key: &str) -> Result<&'a str, String> {
+        self.0.get(key).copied().ok_or_else(|| format!("Missing {}", key))
+    }
+
+    /// The value, or `None` when it is `none`.
+    fn optional(&self, key: &str) -> Result<Option<&'a str>, String> {
+        self.get(key).map(|value| (value != "none").then_some(value))
+    }
+
+    fn number<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        number(self.get(key)?)
+    }
+
+    fn numbers<T: FromStr>(&self, key: &str, count: usize) -> Result<Vec<T>, String> {
+        let value = self.get(key)?;
+        let numbers: Vec<T> = value.split_whitespace().map(number).collect::<Result<_, _>>()?;
+        if numbers.len() != count {
+            return Err(format!("Invalid {}: {}", key, value));
+        }
+        Ok(numbers)
+    }
+
+    fn flag(&self, key: &str) -> Result<bool, String> {
+        flag(self.get(key)?)
+    }
+}
+
+fn number<T: FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("Invalid number: {}", text))
+}
+
+fn flag(text: &str) -> Result<bool, String> {
+    match text {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!("Invalid flag: {}", text)),
+    }
+}
+
+fn letter(id: u8) -> String {
+    letter_from_id(id).to_string()
+}
+
+fn piece_id(text: &str) -> Result<u8, String> {
+    let mut letters = text.chars();
+    match (letters.next().and_then(id_from_letter), letters.next()) {
+        (Some(id), None) if id <= 7 => Ok(id),
+        _ => Err(format!("Unknown piece: {}", text)),
+    }
+}
@@ -0,0 +1,164 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+
+use crate::{
+    board, finder,
+    game::{Input, Tetris},
+    mode::GameMode,
+    tetriminos::{letter_from_id, Tetrimino},
+};
+
+/// `tetris sim [--seed N] [--board path] [--file path] [mode...]`: runs the
+/// engine without a window on commands read from the file or stdin, one per
+/// line, printing the game after each of them. The game can start from a text
+/// board, bottom aligned, whose lowercase piece is put into play. The commands
+/// are `left`, `right`, `rotate`, `soft` and `tick`, all taking an optional
+/// count, plus `drop`, `hold`, `spawn` which ticks until the next piece is in
+/// play and `moves` which lists every placement of the current piece with the
+/// commands reaching it.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut seed = rand::random();
+    let mut file = None;
+    let mut board_path = None;
+    let mut args = args.iter();
+    let mut mode_args = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = number(args.next().ok_or("Missing seed")?)?,
+            "--board" => board_path = Some(args.next().ok_or("Missing board")?),
+            "--file" => file = Some(args.next().ok_or("Missing file")?),
+            _ => mode_args.push(arg.clone()),
+        }
+    }
+    let mode = GameMode::from_args(mode_args.into_iter())?;
+
+    let input: Box<dyn BufRead> = match file {
+        Some(path) => Box::new(BufReader::new(File::open(path).map_err(|err| format!("{}: {}", path, err))?)),
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+
+    let mut tetris = Tetris::with_seed(mode, seed);
+    if let Some(path) = board_path {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        load_board(&mut tetris, &text).map_err(|err| format!("{}: {}", path, err))?;
+    }
+    println!("{} with seed {}", mode.name(), seed);
+    print_game(&tetris);
+    for (line_nb, line) in input.lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        println!("> {}", line);
+        run_command(&mut tetris, line).map_err(|err| format!("Line {}: {}", line_nb + 1, err))?;
+        print_game(&tetris);
+    }
+    Ok(())
+}
+
+fn run_command(tetris: &mut Tetris, line: &str) -> Result<(), String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let count = match words.next() {
+        Some(count) => number(count)?,
+        None => 1,
+    };
+    let input = match command {
+        "left" => Some(Input::Left),
+        "right" => Some(Input::Right),
+        "rotate" => Some(Input::Rotate),
+        "soft" => Some(Input::SoftDrop),
+        "drop" => Some(Input::HardDrop),
+        "hold" => Some(Input::Hold),
+        "tick" | "spawn" => None,
+        "moves" => {
+            print_moves(tetris);
+            return Ok(());
+        }
+        _ => return Err(format!("Unknown command: {}", command)),
+    };
+
+    match input {
+        Some(input) => (0..count).for_each(|_| tetris.apply(input)),
+        None if command == "tick" => (0..count).for_each(|_| tetris.update()),
+        None => {
+            while tetris.current_piece.is_none() && !tetris.game_over && !tetris.is_finished() {
+                tetris.update();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_board(tetris: &mut Tetris, text: &str) -> Result<(), String> {
+    let (rows, piece) = board::parse(text)?;
+    let top = tetris.game_map.len().checked_sub(rows.len());
+    let top = match top {
+        Some(top) if rows[0].len() == tetris.game_map[0].len() => top,
+        _ => return Err("The board must be at most 16 lines of 10 cells".to_owned()),
+    };
+    tetris.game_map[top..].clone_from_slice(&rows);
+    if let Some(mut piece) = piece {
+        piece.y += top;
+        tetris.current_piece = Some(piece);
+    }
+    Ok(())
+}
+
+fn print_moves(tetris: &Tetris) {
+    let Some(ref piece) = tetris.current_piece else {
+        println!("No piece in play");
+        return;
+    };
+    for found in finder::find_moves(&tetris.game_map, piece) {
+        let mut commands: Vec<(&str, usize)> = Vec::new();
+        for input in found.inputs {
+            let name = command_name(input);
+            match commands.last_mut() {
+                Some((last, count)) if *last == name => *count += 1,
+                _ => commands.push((name, 1)),
+            }
+        }
+        let commands: Vec<String> = commands
+            .iter()
+            .map(|&(name, count)| if count > 1 { format!("{} {}", name, count) } else { name.to_owned() })
+            .collect();
+        println!("x {} y {} r {}: {}", found.x, found.y, found.rotation, commands.join(", "));
+    }
+}
+
+fn command_name(input: Input) -> &'static str {
+    match input {
+        Input::Left => "left",
+        Input::Right => "right",
+        Input::Rotate => "rotate",
+        Input::SoftDrop => "soft",
+        Input::HardDrop => "drop",
+        Input::Hold => "hold",
+    }
+}
+
+fn print_game(tetris: &Tetris) {
+    print!("{}", board::to_text(&tetris.game_map, tetris.current_piece.as_ref()));
+
+    let piece = |piece: &Option<Tetrimino>| piece.as_ref().map_or('-', |piece| letter_from_id(piece.id()));
+    println!(
+        "Tick {} Score {} Lines {} Level {} Next {} Hold {}",
+        tetris.frame,
+        tetris.score,
+        tetris.nb_lines,
+        tetris.current_level,
+        piece(&tetris.next_piece),
+        piece(&tetris.hold_piece)
+    );
+    if tetris.game_over {
+        println!("Game over");
+    } else if tetris.is_finished() {
+        println!("{} complete!", tetris.mode.name());
+    }
+}
+
+fn number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("Invalid number: {}", text))
+}
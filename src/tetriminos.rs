@@ -7,16 +7,18 @@ use crate::config::TETRIS_HEIGHT;
 pub type Piece = Vec<Vec<u8>>;
 pub type States = Vec<Piece>;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Tetrimino {
     pub states: States,
     pub x: isize,
     pub y: usize,
     pub current_state: u8,
+    /// Number of board cells each block spans in both directions, 2 in big mode.
+    pub scale: usize,
 }
 
 impl Tetrimino {
-    pub fn rotate(&mut self, game_map: &[Vec<u8>]) {
+    pub fn rotate(&mut self, game_map: &[Vec<u8>]) -> bool {
         let mut tmp_state = self.current_state + 1;
         if tmp_state as usize >= self.states.len() {
             tmp_state = 0;
@@ -24,12 +26,14 @@ impl Tetrimino {
 
         let x_pos = [0, -1, 1, -2, 2, -3];
         for x in x_pos.iter() {
+            let x = x * self.scale as isize;
             if self.test_position(game_map, tmp_state as usize, self.x + x, self.y) {
                 self.current_state = tmp_state;
                 self.x += x;
-                break;
+                return true;
             }
         }
+        false
     }
 
     pub fn change_position(&mut self, game_map: &[Vec<u8>], new_x: isize, new_y: usize) -> bool {
@@ -47,24 +51,57 @@ impl Tetrimino {
     }
 
     pub fn test_position(&self, game_map: &[Vec<u8>], tmp_state: usize, x: isize, y: usize) -> bool {
-        for decal_y in 0..4 {
-            for decal_x in 0..4 {
-                let x = x + decal_x;
-                if self.states[tmp_state][decal_y][decal_x as usize] != 0
-                    && (y + decal_y >= game_map.len()
-                        || x < 0
-                        || x as usize >= game_map[y + decal_y].len()
-                        || game_map[y + decal_y][x as usize] != 0)
-                {
-                    return false;
+        for (x, y, _) in self.cells(tmp_state, x, y) {
+            if y >= game_map.len()
+                || x < 0
+                || x as usize >= game_map[y].len()
+                || game_map[y][x as usize] != 0
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Board cells covered by the piece in the given state and position, with
+    /// their colour.
+    pub fn cells(&self, state: usize, x: isize, y: usize) -> Vec<(isize, usize, u8)> {
+        let mut cells = Vec::new();
+        for (decal_y, line) in self.states[state].iter().enumerate() {
+            for (decal_x, case) in line.iter().enumerate() {
+                if *case == 0 {
+                    continue;
+                }
+                for sub_y in 0..self.scale {
+                    for sub_x in 0..self.scale {
+                        cells.push((
+                            x + (decal_x * self.scale + sub_x) as isize,
+                            y + decal_y * self.scale + sub_y,
+                            *case,
+                        ));
+                    }
                 }
             }
         }
+        cells
+    }
 
-        return true;
+    /// Turns the piece into its big mode version, centred on a board of
+    /// `board_width` cells.
+    pub fn enlarge(mut self, board_width: usize) -> Tetrimino {
+        let width = self.states[0]
+            .iter()
+            .filter_map(|line| line.iter().rposition(|case| *case != 0))
+            .max()
+            .map_or(0, |last| last + 1);
+        self.scale = 2;
+        self.x = ((board_width / 2 - width).div_ceil(2) * 2) as isize;
+        self
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>, textures: &[Texture<'_>; 8], grid_x: i32, grid_y: i32) {
+    pub fn draw(&self, canvas: &mut Canvas<Window>, textures: &[Texture<'_>; 9], grid_x: i32, grid_y: i32) {
+        let size = (TETRIS_HEIGHT * self.scale) as u32;
         for (line_nb, line) in self.states[self.current_state as usize].iter().enumerate() {
             for (case_nb, case) in line.iter().enumerate() {
                 if *case == 0 {
@@ -75,59 +112,67 @@ impl Tetrimino {
                     &textures[*case as usize - 1],
                     None,
                     Rect::new(
-                        grid_x + (self.x + case_nb as isize) as i32 * TETRIS_HEIGHT as i32,
-                        grid_y + (self.y + line_nb) as i32 * TETRIS_HEIGHT as i32,
-                        TETRIS_HEIGHT as u32,
-                        TETRIS_HEIGHT as u32,
+                        grid_x + (self.x + (case_nb * self.scale) as isize) as i32 * TETRIS_HEIGHT as i32,
+                        grid_y + (self.y + line_nb * self.scale) as i32 * TETRIS_HEIGHT as i32,
+                        size,
+                        size,
                     )
                 ).expect("Failed to draw piece");
 
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
-                        grid_x + (self.x + case_nb as isize) as i32 * TETRIS_HEIGHT as i32,
-                        grid_y + (self.y + line_nb) as i32 * TETRIS_HEIGHT as i32,
-                        TETRIS_HEIGHT as u32,
+                        grid_x + (self.x + (case_nb * self.scale) as isize) as i32 * TETRIS_HEIGHT as i32,
+                        grid_y + (self.y + line_nb * self.scale) as i32 * TETRIS_HEIGHT as i32,
+                        size,
                         2,
                     )
                 ).expect("Failed to draw piece");
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
-                        grid_x + (self.x + case_nb as isize) as i32 * TETRIS_HEIGHT as i32,
-                        grid_y + (self.y + line_nb) as i32 * TETRIS_HEIGHT as i32 + (TETRIS_HEIGHT as i32 - 2),
-                        TETRIS_HEIGHT as u32,
+                        grid_x + (self.x + (case_nb * self.scale) as isize) as i32 * TETRIS_HEIGHT as i32,
+                        grid_y + (self.y + line_nb * self.scale) as i32 * TETRIS_HEIGHT as i32 + (size as i32 - 2),
+                        size,
                         2,
                     )
                 ).expect("Failed to draw piece");
         
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
-                        grid_x + (self.x + case_nb as isize) as i32 * TETRIS_HEIGHT as i32,
-                        grid_y + (self.y + line_nb) as i32 * TETRIS_HEIGHT as i32,
+                        grid_x + (self.x + (case_nb * self.scale) as isize) as i32 * TETRIS_HEIGHT as i32,
+                        grid_y + (self.y + line_nb * self.scale) as i32 * TETRIS_HEIGHT as i32,
                         2,
-                        TETRIS_HEIGHT as u32,
+                        size,
                     )
                 ).expect("Failed to draw piece");
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
-                        grid_x + (self.x + case_nb as isize) as i32 * TETRIS_HEIGHT as i32 + (TETRIS_HEIGHT as i32 - 2),
-                        grid_y + (self.y + line_nb) as i32 * TETRIS_HEIGHT as i32,
+                        grid_x + (self.x + (case_nb * self.scale) as isize) as i32 * TETRIS_HEIGHT as i32 + (size as i32 - 2),
+                        grid_y + (self.y + line_nb * self.scale) as i32 * TETRIS_HEIGHT as i32,
                         2,
-                        TETRIS_HEIGHT as u32,
+                        size,
                     )
                 ).expect("Failed to draw piece");
             }
         }
     }
 
-    pub fn preview(&self, canvas: &mut Canvas<Window>, textures: &[Texture<'_>; 8], grid_x: i32, height: u32) {
+    pub fn id(&self) -> u8 {
+        self.states[0].iter().flatten().copied().find(|case| *case != 0).unwrap_or(0)
+    }
+
+    pub fn preview(&self, canvas: &mut Canvas<Window>, textures: &[Texture<'_>; 9], grid_x: i32, height: u32) {
+        self.preview_at(canvas, textures, grid_x + TETRIS_HEIGHT as i32 * 10 + 20, height as i32 / 2);
+    }
+
+    pub fn preview_at(&self, canvas: &mut Canvas<Window>, textures: &[Texture<'_>; 9], x: i32, y: i32) {
         for (line_nb, line) in self.states[self.current_state as usize].iter().enumerate() {
             for (case_nb, case) in line.iter().enumerate() {
                 if *case == 0 {
@@ -138,50 +183,50 @@ impl Tetrimino {
                     &textures[*case as usize - 1],
                     None,
                     Rect::new(
-                        grid_x + TETRIS_HEIGHT as i32 * 10 + 20 + case_nb as i32 * TETRIS_HEIGHT as i32,
-                        height as i32 / 2 + line_nb as i32 * TETRIS_HEIGHT as i32,
+                        x + case_nb as i32 * TETRIS_HEIGHT as i32,
+                        y + line_nb as i32 * TETRIS_HEIGHT as i32,
                         TETRIS_HEIGHT as u32,
                         TETRIS_HEIGHT as u32,
                     )
                 ).expect("Failed to draw piece");
 
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
-                        grid_x + TETRIS_HEIGHT as i32 * 10 + 20 + case_nb as i32 * TETRIS_HEIGHT as i32,
-                        height as i32 / 2 + line_nb as i32 * TETRIS_HEIGHT as i32,
+                        x + case_nb as i32 * TETRIS_HEIGHT as i32,
+                        y + line_nb as i32 * TETRIS_HEIGHT as i32,
                         TETRIS_HEIGHT as u32,
                         2,
                     )
                 ).expect("Failed to draw piece");
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
-                        grid_x + TETRIS_HEIGHT as i32 * 10 + 20 + case_nb as i32 * TETRIS_HEIGHT as i32,
-                        height as i32 / 2 + line_nb as i32 * TETRIS_HEIGHT as i32 + (TETRIS_HEIGHT as i32 - 2),
+                        x + case_nb as i32 * TETRIS_HEIGHT as i32,
+                        y + line_nb as i32 * TETRIS_HEIGHT as i32 + (TETRIS_HEIGHT as i32 - 2),
                         TETRIS_HEIGHT as u32,
                         2,
                     )
                 ).expect("Failed to draw piece");
 
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
-                        grid_x + TETRIS_HEIGHT as i32 * 10 + 20 + case_nb as i32 * TETRIS_HEIGHT as i32,
-                        height as i32 / 2 + line_nb as i32 * TETRIS_HEIGHT as i32,
+                        x + case_nb as i32 * TETRIS_HEIGHT as i32,
+                        y + line_nb as i32 * TETRIS_HEIGHT as i32,
                         2,
                         TETRIS_HEIGHT as u32,
                     )
                 ).expect("Failed to draw piece");
                 canvas.copy(
-                    &textures[7],
+                    &textures[8],
                     None,
                     Rect::new(
-                        grid_x + TETRIS_HEIGHT as i32 * 10 + 20 + case_nb as i32 * TETRIS_HEIGHT as i32 + (TETRIS_HEIGHT as i32 - 2),
-                        height as i32 / 2 + line_nb as i32 * TETRIS_HEIGHT as i32,
+                        x + case_nb as i32 * TETRIS_HEIGHT as i32 + (TETRIS_HEIGHT as i32 - 2),
+                        y + line_nb as i32 * TETRIS_HEIGHT as i32,
                         2,
                         TETRIS_HEIGHT as u32,
                     )
@@ -195,6 +240,37 @@ pub trait TetriminoGenerator {
     fn new() -> Tetrimino;
 }
 
+/// Letters naming each cell colour in text boards and puzzles, `G` being garbage.
+pub const LETTERS: [char; 8] = ['I', 'J', 'L', 'O', 'S', 'Z', 'T', 'G'];
+
+pub fn id_from_letter(letter: char) -> Option<u8> {
+    LETTERS
+        .iter()
+        .position(|other| *other == letter.to_ascii_uppercase())
+        .map(|index| index as u8 + 1)
+}
+
+/// Letter of a cell, `.` for an empty one.
+pub fn letter_from_id(id: u8) -> char {
+    match id {
+        0 => '.',
+        id => LETTERS[id as usize - 1],
+    }
+}
+
+pub fn create_tetrimino(id: u8) -> Tetrimino {
+    match id {
+        1 => TetriminoI::new(),
+        2 => TetriminoJ::new(),
+        3 => TetriminoL::new(),
+        4 => TetriminoO::new(),
+        5 => TetriminoS::new(),
+        6 => TetriminoZ::new(),
+        7 => TetriminoT::new(),
+        _ => unreachable!(),
+    }
+}
+
 pub struct TetriminoI;
 
 impl TetriminoGenerator for TetriminoI {
@@ -217,6 +293,7 @@ impl TetriminoGenerator for TetriminoI {
             x: 4,
             y: 0,
             current_state: 0,
+            scale: 1,
         }
     }
 }
@@ -255,6 +332,7 @@ impl TetriminoGenerator for TetriminoJ {
             x: 4,
             y: 0,
             current_state: 0,
+            scale: 1,
         }
     }
 }
@@ -293,6 +371,7 @@ impl TetriminoGenerator for TetriminoL {
             x: 4,
             y: 0,
             current_state: 0,
+            scale: 1,
         }
     }
 }
@@ -311,6 +390,7 @@ impl TetriminoGenerator for TetriminoO {
             x: 5,
             y: 0,
             current_state: 0,
+            scale: 1,
         }
     }
 }
@@ -337,6 +417,7 @@ impl TetriminoGenerator for TetriminoS {
             x: 4,
             y: 0,
             current_state: 0,
+            scale: 1,
         }
     }
 }
@@ -363,6 +444,7 @@ impl TetriminoGenerator for TetriminoZ {
             x: 4,
             y: 0,
             current_state: 0,
+            scale: 1,
         }
     }
 }
@@ -401,6 +483,7 @@ impl TetriminoGenerator for TetriminoT {
             x: 4,
             y: 0,
             current_state: 0,
+            scale: 1,
         }
     }
 }
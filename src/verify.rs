@@ -0,0 +1,100 @@
+use crate::{
+    config::FPS,
+    game::Tetris,
+    mode::format_time,
+    replay::{Playback, Replay},
+    save,
+};
+
+/// The result a player says a replay reaches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Claim {
+    pub score: u32,
+    pub lines: u32,
+    /// Milliseconds, `None` to leave the time unchecked.
+    pub time: Option<u32>,
+}
+
+/// State hash as each piece came into play.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub piece: u32,
+    pub frame: u32,
+    pub hash: u64,
+}
+
+/// Ticks a replay may run on after its last input, far longer than falling
+/// pieces take to top out.
+const IDLE_LIMIT: u32 = 10 * 60 * FPS;
+
+/// Plays the replay headlessly, hashing the whole engine state every time a
+/// new piece comes into play. Returns the game as it ended with the trail,
+/// or an error if the game would keep going long after the last input.
+pub fn hash_trail(replay: &Replay) -> Result<(Tetris, Vec<Step>), String> {
+    let last_input = replay.inputs.last().map_or(0, |(frame, _, _)| *frame);
+    let mut playback = Playback::new(replay);
+    let mut trail = Vec::new();
+    while playback.step() {
+        let tetris = &mut playback.tetris;
+        if trail.last().map_or(0, |step: &Step| step.piece) != tetris.nb_pieces {
+            trail.push(Step {
+                piece: tetris.nb_pieces,
+                frame: tetris.frame,
+                hash: state_hash(tetris),
+            });
+        }
+        if tetris.frame <= last_input {
+            continue;
+        }
+        // Without gravity a piece left alone never moves, only the clock runs.
+        if tetris.gravity() == 0 && tetris.current_piece.is_some() {
+            tetris.frame = replay.length;
+            break;
+        }
+        if tetris.frame - last_input > IDLE_LIMIT {
+            return Err(format!(
+                "The game is still going {} after the last input",
+                format_time(IDLE_LIMIT / FPS * 1000)
+            ));
+        }
+    }
+    Ok((playback.tetris, trail))
+}
+
+/// FNV-1a of the saved state, which covers the board, pieces, randomizer,
+/// counters and timers alike.
+pub fn state_hash(tetris: &Tetris) -> u64 {
+    let text = save::to_text(tetris).unwrap_or_default();
+    text.bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Checks a claim against the replay, printing the hash trail and each
+/// compared value. Returns whether everything matches.
+pub fn verify(replay: &Replay, claim: Claim) -> Result<bool, String> {
+    let (tetris, trail) = hash_trail(replay)?;
+    for step in &trail {
+        println!("Piece {} at tick {}: {:016x}", step.piece, step.frame, step.hash);
+    }
+
+    let mut checks = vec![
+        ("Score", tetris.score.to_string(), claim.score.to_string()),
+        ("Lines", tetris.nb_lines.to_string(), claim.lines.to_string()),
+    ];
+    if let Some(time) = claim.time {
+        checks.push(("Time", format_time(tetris.elapsed_millis()), format_time(time)));
+    }
+    // A replay whose recorded result was edited no longer plays out to it.
+    checks.push(("Recorded score", tetris.score.to_string(), replay.score.to_string()));
+    checks.push(("Recorded lines", tetris.nb_lines.to_string(), replay.lines.to_string()));
+    checks.push(("Length", tetris.frame.to_string(), replay.length.to_string()));
+
+    let mut valid = true;
+    for (name, actual, claimed) in checks {
+        let status = if actual == claimed { "ok" } else { "MISMATCH" };
+        println!("{}: {} (claimed {}) {}", name, actual, claimed, status);
+        valid &= actual == claimed;
+    }
+    println!("{}", if valid { "Verified" } else { "Rejected" });
+    Ok(valid)
+}
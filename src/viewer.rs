@@ -0,0 +1,111 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    config::{FPS, TETRIS_HEIGHT},
+    display_text,
+    mode::format_time,
+    replay::{Playback, Replay},
+    Scene,
+};
+
+const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const SEEK_SECONDS: u32 = 5;
+
+/// Plays a replay in the window until Escape is pressed. Space pauses, Up
+/// and Down change the speed, Left and Right jump 5 seconds, F steps one
+/// frame, N steps one piece and Home starts over. Typing a number then P or
+/// T jumps to that piece or that second.
+pub fn watch(
+    replay: &Replay,
+    canvas: &mut Canvas<Window>,
+    texture_creator: &TextureCreator<WindowContext>,
+    font: &sdl2::ttf::Font,
+    scene: &Scene,
+    event_pump: &mut sdl2::EventPump,
+) {
+    let mut playback = Playback::new(replay);
+    let mut paused = false;
+    let mut speed = 2;
+    let mut typed = String::new();
+    let mut ticks = 0.0;
+    let mut timer = SystemTime::now();
+
+    loop {
+        for event in event_pump.poll_iter() {
+            let frame = playback.tetris.frame;
+            match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return,
+                Event::KeyDown { keycode: Some(keycode), .. } => match keycode {
+                    Keycode::Space => paused = !paused,
+                    Keycode::Up => speed = (speed + 1).min(SPEEDS.len() - 1),
+                    Keycode::Down => speed = speed.saturating_sub(1),
+                    Keycode::Left => playback.seek_frame(frame.saturating_sub(SEEK_SECONDS * FPS)),
+                    Keycode::Right => playback.seek_frame(frame + SEEK_SECONDS * FPS),
+                    Keycode::Home => playback.restart(),
+                    Keycode::F => {
+                        paused = true;
+                        playback.step();
+                    }
+                    Keycode::N => {
+                        paused = true;
+                        playback.step_piece();
+                    }
+                    Keycode::P | Keycode::T => {
+                        match (keycode, typed.parse::<u32>()) {
+                            (Keycode::P, Ok(nb_pieces)) => playback.seek_piece(nb_pieces),
+                            (_, Ok(seconds)) => playback.seek_frame(seconds * FPS),
+                            _ => {}
+                        }
+                        typed.clear();
+                    }
+                    Keycode::Backspace => {
+                        typed.pop();
+                    }
+                    _ => {
+                        let name = keycode.name();
+                        if name.len() == 1 && name.chars().all(|letter| letter.is_ascii_digit()) {
+                            typed.push_str(&name);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let elapsed = timer.elapsed().unwrap_or_default();
+        timer = SystemTime::now();
+        if !paused {
+            ticks += elapsed.as_secs_f64() * FPS as f64 * SPEEDS[speed];
+            while ticks >= 1.0 && playback.step() {
+                ticks -= 1.0;
+            }
+            if playback.is_done() {
+                ticks = 0.0;
+            }
+        }
+
+        scene.draw(canvas, texture_creator, font, &playback.tetris);
+        let start_x_point = scene.grid_x + TETRIS_HEIGHT as i32 * 10 + 20;
+        let status = match (playback.is_done(), paused) {
+            (true, _) => "Replay over".to_owned(),
+            (_, true) => "Paused".to_owned(),
+            _ => format!("Playing {}x", SPEEDS[speed]),
+        };
+        let position = if typed.is_empty() {
+            format_time(playback.tetris.elapsed_millis())
+        } else {
+            format!("Go to {}", typed)
+        };
+        display_text(canvas, texture_creator, font, &status, start_x_point, 680);
+        display_text(canvas, texture_creator, font, &format!("Piece {}", playback.tetris.nb_pieces), start_x_point, 715);
+        display_text(canvas, texture_creator, font, &position, start_x_point, 750);
+        canvas.present();
+
+        sleep(Duration::from_millis(1000 / 60));
+    }
+}